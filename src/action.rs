@@ -0,0 +1,385 @@
+//! Named actions and the keys, gamepad buttons and sticks bound to them.
+
+use std::{collections::HashMap, fmt, marker::PhantomData};
+
+use bevy::{prelude::*, utils::HashSet};
+use serde::{
+    de::{self, IntoDeserializer, MapAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+use tracing::instrument;
+
+use crate::config;
+
+const KEYBINDINGS_FILE_NAME: &str = "keybindings.ron";
+/// Bumped whenever older `keybindings.ron` files need upgrading when they're loaded.
+///
+/// 1. `LeaveTown` was folded into `Interact`, since town is left from the stagecoach stop.
+const KEYBINDINGS_VERSION: u32 = 1;
+
+/// Something the player can do, independent of which keys are bound to it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveUp,
+    MoveLeft,
+    MoveDown,
+    MoveRight,
+    Interact,
+    Pause,
+    /// Open or close the inventory panel.
+    Inventory,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::MoveUp,
+        Action::MoveLeft,
+        Action::MoveDown,
+        Action::MoveRight,
+        Action::Interact,
+        Action::Pause,
+        Action::Inventory,
+    ];
+
+    /// Direction of the analog stick or D-pad that triggers this action, if any.
+    fn direction(self) -> Option<Vec2> {
+        match self {
+            Action::MoveUp => Some(Vec2::Y),
+            Action::MoveLeft => Some(-Vec2::X),
+            Action::MoveDown => Some(-Vec2::Y),
+            Action::MoveRight => Some(Vec2::X),
+            _ => None,
+        }
+    }
+
+    fn default_keys(self) -> Vec<KeyCode> {
+        match self {
+            Action::MoveUp => vec![KeyCode::W],
+            Action::MoveLeft => vec![KeyCode::A],
+            Action::MoveDown => vec![KeyCode::S],
+            Action::MoveRight => vec![KeyCode::D],
+            Action::Interact => vec![KeyCode::E],
+            Action::Pause => vec![KeyCode::Escape],
+            Action::Inventory => vec![KeyCode::I],
+        }
+    }
+
+    fn default_buttons(self) -> Vec<GamepadButtonType> {
+        match self {
+            Action::MoveUp => vec![GamepadButtonType::DPadUp],
+            Action::MoveLeft => vec![GamepadButtonType::DPadLeft],
+            Action::MoveDown => vec![GamepadButtonType::DPadDown],
+            Action::MoveRight => vec![GamepadButtonType::DPadRight],
+            Action::Interact => vec![GamepadButtonType::South],
+            Action::Pause => vec![GamepadButtonType::Start],
+            Action::Inventory => vec![GamepadButtonType::North],
+        }
+    }
+}
+
+/// How the left analog stick is turned into movement.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct StickSettings {
+    /// Stick deflection below which input is ignored, from 0 to 1.
+    pub deadzone: f32,
+    /// How far past the deadzone the stick must point along a direction to press its action.
+    pub direction_threshold: f32,
+}
+
+impl Default for StickSettings {
+    fn default() -> Self {
+        Self {
+            deadzone: 0.15,
+            direction_threshold: 0.5,
+        }
+    }
+}
+
+impl StickSettings {
+    /// Removes the deadzone from a raw stick position, rescaling the rest of the range to 0..=1.
+    fn apply(&self, raw: Vec2) -> Vec2 {
+        let length = raw.length().min(1.);
+        if length <= self.deadzone {
+            return Vec2::ZERO;
+        }
+
+        raw.normalize() * (length - self.deadzone) / (1. - self.deadzone)
+    }
+}
+
+/// Maps each [`Action`] to the keys and gamepad buttons that trigger it, loaded from
+/// `keybindings.ron` in the config directory.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ActionMap {
+    /// [`KEYBINDINGS_VERSION`] when the file was written. Files from before it was added have none.
+    #[serde(default)]
+    pub version: u32,
+    #[serde(deserialize_with = "deserialize_bindings")]
+    pub keys: HashMap<Action, Vec<KeyCode>>,
+    #[serde(deserialize_with = "deserialize_bindings")]
+    pub buttons: HashMap<Action, Vec<GamepadButtonType>>,
+    pub stick: StickSettings,
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        Self {
+            version: KEYBINDINGS_VERSION,
+            keys: Action::ALL
+                .into_iter()
+                .map(|action| (action, action.default_keys()))
+                .collect(),
+            buttons: Action::ALL
+                .into_iter()
+                .map(|action| (action, action.default_buttons()))
+                .collect(),
+            stick: StickSettings::default(),
+        }
+    }
+}
+
+impl ActionMap {
+    /// Name of the first key bound to an action, for showing in prompts.
+    pub fn key_name(&self, action: Action) -> String {
+        self.keys
+            .get(&action)
+            .and_then(|keys| keys.first())
+            .map_or_else(|| format!("{action:?}"), |key| format!("{key:?}"))
+    }
+
+    pub fn load() -> Self {
+        let mut action_map: Self = config::load(KEYBINDINGS_FILE_NAME);
+
+        // Config files written before an action existed won't mention it
+        for action in Action::ALL {
+            action_map
+                .keys
+                .entry(action)
+                .or_insert_with(|| action.default_keys());
+            action_map
+                .buttons
+                .entry(action)
+                .or_insert_with(|| action.default_buttons());
+        }
+
+        if action_map.version < KEYBINDINGS_VERSION {
+            info!(
+                from = action_map.version,
+                to = KEYBINDINGS_VERSION,
+                "upgrading keybindings"
+            );
+            action_map.version = KEYBINDINGS_VERSION;
+            if let Err(error) = config::save(KEYBINDINGS_FILE_NAME, &action_map) {
+                warn!(?error, "failed to save upgraded keybindings");
+            }
+        }
+
+        action_map
+    }
+}
+
+/// Reads the bindings for each action, moving bindings for actions that have since been folded
+/// into another onto that action, and skipping any that are no longer known.
+fn deserialize_bindings<'de, D, T>(deserializer: D) -> Result<HashMap<Action, Vec<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + PartialEq,
+{
+    struct BindingsVisitor<T>(PhantomData<T>);
+
+    impl<'de, T> Visitor<'de> for BindingsVisitor<T>
+    where
+        T: Deserialize<'de> + PartialEq,
+    {
+        type Value = HashMap<Action, Vec<T>>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a map of actions to their bindings")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut bindings: Self::Value = HashMap::new();
+            while let Some(ActionName(name)) = map.next_key()? {
+                let parsed: Result<Action, de::value::Error> = match name.as_str() {
+                    "LeaveTown" => Ok(Action::Interact),
+                    name => Action::deserialize(name.into_deserializer()),
+                };
+                let action = match parsed {
+                    Ok(action) => action,
+                    Err(_) => {
+                        warn!(action = %name, "ignoring bindings for unknown action");
+                        map.next_value::<de::IgnoredAny>()?;
+                        continue;
+                    }
+                };
+
+                let entry = bindings.entry(action).or_default();
+                for binding in map.next_value::<Vec<T>>()? {
+                    if !entry.contains(&binding) {
+                        entry.push(binding);
+                    }
+                }
+            }
+
+            Ok(bindings)
+        }
+    }
+
+    deserializer.deserialize_map(BindingsVisitor(PhantomData))
+}
+
+/// Name of an action as written in `keybindings.ron`, which may not be an [`Action`] any more.
+struct ActionName(String);
+
+impl<'de> Deserialize<'de> for ActionName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct NameVisitor;
+
+        impl<'de> Visitor<'de> for NameVisitor {
+            type Value = ActionName;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an action name")
+            }
+
+            fn visit_str<E: de::Error>(self, name: &str) -> Result<Self::Value, E> {
+                Ok(ActionName(name.to_string()))
+            }
+        }
+
+        deserializer.deserialize_identifier(NameVisitor)
+    }
+}
+
+/// Gamepads that are currently connected.
+#[derive(Default, Debug)]
+pub struct ConnectedGamepads(pub HashSet<Gamepad>);
+
+/// Position of the left analog stick with the deadzone removed, so its length is how hard the
+/// player is pushing.
+#[derive(Default, Debug)]
+pub struct LeftStick(pub Vec2);
+
+#[instrument(skip(events, gamepads))]
+pub fn track_gamepads(
+    mut events: EventReader<GamepadEvent>,
+    mut gamepads: ResMut<ConnectedGamepads>,
+) {
+    for GamepadEvent(gamepad, event_type) in events.iter() {
+        match event_type {
+            GamepadEventType::Connected => {
+                info!(?gamepad, "gamepad connected");
+                gamepads.0.insert(*gamepad);
+            }
+            GamepadEventType::Disconnected => {
+                info!(?gamepad, "gamepad disconnected");
+                gamepads.0.remove(gamepad);
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Presses and releases actions in `Input<Action>` according to the keyboard, connected
+/// gamepads and [`ActionMap`].
+#[instrument(skip(
+    keyboard_input,
+    button_input,
+    axes,
+    gamepads,
+    action_map,
+    stick,
+    actions
+))]
+pub fn map_actions(
+    keyboard_input: Res<Input<KeyCode>>,
+    button_input: Res<Input<GamepadButton>>,
+    axes: Res<Axis<GamepadAxis>>,
+    gamepads: Res<ConnectedGamepads>,
+    action_map: Res<ActionMap>,
+    mut stick: ResMut<LeftStick>,
+    mut actions: ResMut<Input<Action>>,
+) {
+    actions.clear();
+
+    let axis = |gamepad: Gamepad, axis_type| {
+        axes.get(GamepadAxis(gamepad, axis_type))
+            .unwrap_or_default()
+    };
+
+    stick.0 = gamepads
+        .0
+        .iter()
+        .map(|gamepad| {
+            let raw = Vec2::new(
+                axis(*gamepad, GamepadAxisType::LeftStickX),
+                axis(*gamepad, GamepadAxisType::LeftStickY),
+            );
+            action_map.stick.apply(raw)
+        })
+        .find(|position| *position != Vec2::ZERO)
+        .unwrap_or(Vec2::ZERO);
+
+    // Some controllers report their D-pad as a pair of axes rather than buttons
+    let d_pads = gamepads
+        .0
+        .iter()
+        .map(|gamepad| {
+            Vec2::new(
+                axis(*gamepad, GamepadAxisType::DPadX),
+                axis(*gamepad, GamepadAxisType::DPadY),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    for action in Action::ALL {
+        let key_pressed = action_map.keys.get(&action).map_or(false, |keys| {
+            keys.iter().any(|key| keyboard_input.pressed(*key))
+        });
+        let button_pressed = action_map.buttons.get(&action).map_or(false, |buttons| {
+            buttons.iter().any(|button| {
+                gamepads
+                    .0
+                    .iter()
+                    .any(|gamepad| button_input.pressed(GamepadButton(*gamepad, *button)))
+            })
+        });
+        let axis_pressed = action.direction().map_or(false, |direction| {
+            stick.0.dot(direction) > action_map.stick.direction_threshold
+                || d_pads.iter().any(|d_pad| d_pad.dot(direction) > 0.5)
+        });
+
+        if key_pressed || button_pressed || axis_pressed {
+            actions.press(action);
+        } else if actions.pressed(action) {
+            actions.release(action);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn leave_town_bindings_move_to_interact() {
+        let action_map: ActionMap = ron::de::from_str(
+            "(keys: {Interact: [Space], LeaveTown: [E], Jump: [W]}, buttons: {Interact: [South], \
+             LeaveTown: [West, South]})",
+        )
+        .unwrap();
+
+        assert_eq!(action_map.version, 0);
+        assert_eq!(
+            action_map.keys[&Action::Interact],
+            [KeyCode::Space, KeyCode::E]
+        );
+        assert_eq!(action_map.keys.len(), 1);
+        assert_eq!(
+            action_map.buttons[&Action::Interact],
+            [GamepadButtonType::South, GamepadButtonType::West]
+        );
+    }
+}
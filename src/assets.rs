@@ -17,14 +17,21 @@ pub struct Sprites {
 pub struct Images {
     #[asset(path = "images/buildings.png")]
     pub buildings: Handle<Image>,
-    #[asset(path = "images/head.png")]
-    pub head: Handle<Image>,
-    #[asset(path = "images/tail.png")]
-    pub tail: Handle<Image>,
+    /// Snake pieces: head, straight body, corner and tail tip.
+    #[asset(path = "images/snake.png")]
+    pub snake: Handle<Image>,
     #[asset(path = "images/letter.png")]
     pub letter: Handle<Image>,
+    #[asset(path = "images/parcel.png")]
+    pub parcel: Handle<Image>,
+    #[asset(path = "images/express.png")]
+    pub express: Handle<Image>,
     #[asset(path = "images/snakebackground.png")]
     pub snakebackground: Handle<Image>,
+    #[asset(path = "images/border.png")]
+    pub border: Handle<Image>,
+    #[asset(path = "images/obstacles.png")]
+    pub obstacles: Handle<Image>,
     #[asset(path = "images/meowdyend1.png")]
     pub end_1: Handle<Image>,
     #[asset(path = "images/meowdyend2.png")]
@@ -0,0 +1,87 @@
+//! Config and data file locations, and reading and writing the RON files kept there.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use bevy::prelude::*;
+use directories::ProjectDirs;
+use ron::ser::PrettyConfig;
+use serde::{de::DeserializeOwned, Serialize};
+
+fn project_dirs() -> Option<ProjectDirs> {
+    ProjectDirs::from("", "", "meowdy")
+}
+
+/// The game's config directory, e.g. `~/.config/meowdy` on Linux.
+pub fn config_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.config_dir().to_path_buf())
+}
+
+/// The game's data directory, e.g. `~/.local/share/meowdy` on Linux.
+pub fn data_dir() -> Option<PathBuf> {
+    project_dirs().map(|dirs| dirs.data_dir().to_path_buf())
+}
+
+/// Loads a RON config file from the config directory.
+///
+/// If the file doesn't exist yet, the default config is written out so players have something to
+/// edit. If it can't be read or parsed, the default config is used instead.
+pub fn load<T>(file_name: &str) -> T
+where
+    T: Serialize + DeserializeOwned + Default,
+{
+    let path = match config_dir() {
+        Some(dir) => dir.join(file_name),
+        None => {
+            warn!(file_name, "no config directory, using default config");
+            return T::default();
+        }
+    };
+
+    if !path.exists() {
+        let config = T::default();
+        if let Err(error) = write(&path, &config) {
+            warn!(?error, path = %path.display(), "failed to write default config");
+        } else {
+            info!(path = %path.display(), "wrote default config");
+        }
+        return config;
+    }
+
+    match read(&path) {
+        Ok(config) => {
+            info!(path = %path.display(), "loaded config");
+            config
+        }
+        Err(error) => {
+            warn!(?error, path = %path.display(), "failed to load config, using default");
+            T::default()
+        }
+    }
+}
+
+/// Writes a RON config file to the config directory, e.g. after it was changed in game.
+pub fn save<T: Serialize>(file_name: &str, config: &T) -> anyhow::Result<()> {
+    let dir = config_dir().context("no config directory")?;
+    write(&dir.join(file_name), config)
+}
+
+/// Reads a RON file, e.g. a config file or something kept in the data directory.
+pub fn read<T: DeserializeOwned>(path: &Path) -> anyhow::Result<T> {
+    let contents =
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path.display()))?;
+    ron::de::from_str(&contents).with_context(|| format!("failed to parse {}", path.display()))
+}
+
+/// Writes a RON file, creating the directory it goes in if needed.
+pub fn write<T: Serialize>(path: &Path, value: &T) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create {}", parent.display()))?;
+    }
+    let contents = ron::ser::to_string_pretty(value, PrettyConfig::new())?;
+    fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))
+}
@@ -1,20 +1,67 @@
 //! Motion, position and control.
 
-use bevy::{prelude::*, utils::HashSet};
+use std::marker::PhantomData;
+
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashSet};
 use bevy_rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
 use tracing::instrument;
 
+use crate::action::{Action, ActionMap, LeftStick};
+
+/// Present while something, like a conversation or the ticket booth, has the player's attention.
+/// The player can't move, or talk to anyone or go through doors, while this exists.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InputSuspended;
+
+/// The interact action, for systems in town that respond to it or prompt for it.
+#[derive(SystemParam)]
+pub struct Interact<'w, 's> {
+    actions: Res<'w, Input<Action>>,
+    action_map: Res<'w, ActionMap>,
+    suspended: Option<Res<'w, InputSuspended>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl Interact<'_, '_> {
+    /// Whether interact was just pressed while the player is free to act on it.
+    pub fn just_pressed(&self) -> bool {
+        self.actions.just_pressed(Action::Interact) && !self.is_suspended()
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.is_some()
+    }
+
+    /// Name of the key bound to interact, to show in prompts.
+    pub fn key_name(&self) -> String {
+        self.action_map.key_name(Action::Interact)
+    }
+}
+
 #[derive(Component, Clone, Debug)]
 pub struct Moves {
     pub speed: f32,
 }
 
-#[derive(Component, Clone, Debug, Default)]
+#[derive(Component, Clone, Debug)]
 pub struct Controlled {
     pub inputs: HashSet<ControlInput>,
+    /// Fraction of full speed to move at, less than 1 when an analog stick is only part pushed.
+    pub speed_scale: f32,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+impl Default for Controlled {
+    fn default() -> Self {
+        Self {
+            inputs: HashSet::default(),
+            speed_scale: 1.,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub enum ControlInput {
     Up,
     Left,
@@ -28,21 +75,34 @@ pub enum Facing {
     Left,
 }
 
-#[instrument(skip(query, keyboard_input))]
-pub fn read_control_input(mut query: Query<&mut Controlled>, keyboard_input: Res<Input<KeyCode>>) {
+#[instrument(skip(query, actions, stick, suspended))]
+pub fn read_control_input(
+    mut query: Query<&mut Controlled>,
+    actions: Res<Input<Action>>,
+    stick: Res<LeftStick>,
+    suspended: Option<Res<InputSuspended>>,
+) {
     for mut controlled in query.iter_mut() {
         controlled.inputs.clear();
+        if suspended.is_some() {
+            continue;
+        }
+        controlled.speed_scale = if stick.0 == Vec2::ZERO {
+            1.
+        } else {
+            stick.0.length().min(1.)
+        };
 
-        if keyboard_input.pressed(KeyCode::W) {
+        if actions.pressed(Action::MoveUp) {
             controlled.inputs.insert(ControlInput::Up);
         }
-        if keyboard_input.pressed(KeyCode::A) {
+        if actions.pressed(Action::MoveLeft) {
             controlled.inputs.insert(ControlInput::Left);
         }
-        if keyboard_input.pressed(KeyCode::S) {
+        if actions.pressed(Action::MoveDown) {
             controlled.inputs.insert(ControlInput::Down);
         }
-        if keyboard_input.pressed(KeyCode::D) {
+        if actions.pressed(Action::MoveRight) {
             controlled.inputs.insert(ControlInput::Right);
         }
     }
@@ -93,7 +153,7 @@ pub fn move_controlled(mut query: Query<(&mut RigidBodyVelocityComponent, &Contr
 
         if !(x_axis == 0 && y_axis == 0) {
             rigid_body.linvel.normalize_mut();
-            rigid_body.linvel *= moves.speed;
+            rigid_body.linvel *= moves.speed * controlled.speed_scale;
         }
     }
 }
@@ -0,0 +1,658 @@
+//! Conversations with townsfolk, loaded from `.dialogue` files in `assets/data`.
+
+use std::{collections::HashMap, marker::PhantomData};
+
+use anyhow::bail;
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use tracing::instrument;
+
+use crate::{
+    assets::Fonts,
+    control::{InputSuspended, Interact},
+    focus::Activated,
+    inventory::{Inventory, ItemCatalog},
+    jobs::{JobBoard, Jobs},
+    save::{Progress, SaveData},
+    scene::outside::{
+        npc::{NearbyNpc, Npc, Town, TownData},
+        ticket_booth::TicketBooth,
+        NearbyDoor,
+    },
+    ui::{spawn_choice_button, HIGHLIGHT_COLOR, OVERLAY_PANEL_COLOR},
+    GameState, Meowney,
+};
+
+/// Something that has to be true for a choice to be picked.
+#[derive(Clone, Debug, Deserialize)]
+pub enum Condition {
+    /// The player has at least this much Meowney.
+    HasMeowney(u32),
+    /// A story flag has been set.
+    HasFlag(String),
+    /// A story flag hasn't been set yet.
+    LacksFlag(String),
+    /// The job hasn't been taken on yet.
+    JobAvailable(String),
+    JobActive(String),
+    JobCompleted(String),
+    /// The player is carrying at least one of the item.
+    HasItem(String),
+    /// One more of the item fits in the player's inventory.
+    HasRoomFor(String),
+}
+
+impl Condition {
+    fn reference(&self) -> Option<Reference> {
+        match self {
+            Condition::JobAvailable(job)
+            | Condition::JobActive(job)
+            | Condition::JobCompleted(job) => Some(Reference::Job(job)),
+            Condition::HasItem(item) | Condition::HasRoomFor(item) => Some(Reference::Item(item)),
+            Condition::HasMeowney(_) | Condition::HasFlag(_) | Condition::LacksFlag(_) => None,
+        }
+    }
+
+    pub fn holds(
+        &self,
+        meowney: &Meowney,
+        progress: &Progress,
+        jobs: &Jobs,
+        inventory: &Inventory,
+        items: &ItemCatalog,
+    ) -> bool {
+        match self {
+            Condition::HasMeowney(amount) => meowney.0 >= *amount,
+            Condition::HasFlag(flag) => progress.flags.contains(flag),
+            Condition::LacksFlag(flag) => !progress.flags.contains(flag),
+            Condition::JobAvailable(job) => jobs.is_available(job),
+            Condition::JobActive(job) => jobs.is_active(job),
+            Condition::JobCompleted(job) => jobs.is_completed(job),
+            Condition::HasItem(item) => inventory.has(item),
+            Condition::HasRoomFor(item) => inventory.can_add(items, item, 1),
+        }
+    }
+}
+
+/// A change to the game made by picking a choice or reaching a node.
+#[derive(Clone, Debug, Deserialize)]
+pub enum Effect {
+    GiveMeowney(u32),
+    /// Takes Meowney, as far as the player has any.
+    TakeMeowney(u32),
+    SetFlag(String),
+    ClearFlag(String),
+    /// Takes on a job from `assets/data/town.jobs`.
+    StartJob(String),
+    /// Gives the player some of an item from `assets/data/town.items`.
+    GiveItem(String, u32),
+    /// Takes some of an item, if the player has that many.
+    TakeItem(String, u32),
+    /// Ends the conversation and heads into the post office for a shift.
+    StartPostOfficeShift,
+    /// Opens the stagecoach ticket booth.
+    OpenTicketBooth,
+}
+
+/// The parts of the game dialogue effects can change.
+#[derive(SystemParam)]
+pub struct EffectTarget<'w, 's> {
+    pub meowney: ResMut<'w, Meowney>,
+    pub progress: ResMut<'w, Progress>,
+    pub jobs: ResMut<'w, Jobs>,
+    pub inventory: ResMut<'w, Inventory>,
+    pub app_state: ResMut<'w, State<GameState>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl Effect {
+    fn reference(&self) -> Option<Reference> {
+        match self {
+            Effect::StartJob(job) => Some(Reference::Job(job)),
+            Effect::GiveItem(item, _) | Effect::TakeItem(item, _) => Some(Reference::Item(item)),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, commands: &mut Commands, target: &mut EffectTarget, items: &ItemCatalog) {
+        debug!(effect = ?self, "applying dialogue effect");
+        match self {
+            Effect::GiveMeowney(amount) => target.meowney.0 += amount,
+            Effect::TakeMeowney(amount) => {
+                target.meowney.0 = target.meowney.0.saturating_sub(*amount);
+            }
+            Effect::SetFlag(flag) => {
+                target.progress.flags.insert(flag.clone());
+            }
+            Effect::ClearFlag(flag) => {
+                target.progress.flags.remove(flag);
+            }
+            Effect::StartJob(job) => target.jobs.start(job),
+            Effect::GiveItem(item, count) => {
+                target.inventory.add(items, item, *count);
+            }
+            Effect::TakeItem(item, count) => {
+                target.inventory.remove(item, *count);
+            }
+            Effect::StartPostOfficeShift => {
+                if let Err(error) = target.app_state.set(GameState::PostOfficePrompt) {
+                    warn!(?error, "failed to start post office shift");
+                }
+            }
+            Effect::OpenTicketBooth => TicketBooth::Selling.open(commands),
+        }
+    }
+}
+
+/// A job or item named in dialogue, which has to exist in the rest of the town data.
+#[derive(Clone, Copy, Debug)]
+enum Reference<'a> {
+    Job(&'a str),
+    Item(&'a str),
+}
+
+impl Reference<'_> {
+    fn check(self, jobs: &JobBoard, items: &ItemCatalog) -> anyhow::Result<()> {
+        match self {
+            Reference::Job(job) if jobs.get(job).is_none() => {
+                bail!("job {job:?} is not on the job board")
+            }
+            Reference::Item(item) if items.get(item).is_none() => {
+                bail!("item {item:?} is not in the item catalog")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// An answer the player can give.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Choice {
+    pub text: String,
+    /// Node to go to next, or `None` to end the conversation.
+    #[serde(default)]
+    pub next: Option<String>,
+    /// All of these have to hold for the choice to be picked.
+    #[serde(default)]
+    pub conditions: Vec<Condition>,
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+}
+
+/// One line of a conversation and the answers to it.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DialogueNode {
+    pub text: String,
+    /// Applied whenever the conversation reaches this node.
+    #[serde(default)]
+    pub effects: Vec<Effect>,
+    /// With no choices, the player can only say goodbye.
+    #[serde(default)]
+    pub choices: Vec<Choice>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DialogueTree {
+    /// Node the conversation opens with.
+    pub start: String,
+    pub nodes: HashMap<String, DialogueNode>,
+}
+
+impl DialogueTree {
+    fn validate(&self) -> anyhow::Result<()> {
+        if !self.nodes.contains_key(&self.start) {
+            bail!("start node {:?} does not exist", self.start);
+        }
+        for (name, node) in &self.nodes {
+            for next in node
+                .choices
+                .iter()
+                .filter_map(|choice| choice.next.as_ref())
+            {
+                if !self.nodes.contains_key(next) {
+                    bail!("node {name:?} leads to {next:?}, which does not exist");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn can_pick(
+        &self,
+        choice: &Choice,
+        meowney: &Meowney,
+        progress: &Progress,
+        jobs: &Jobs,
+        inventory: &Inventory,
+        items: &ItemCatalog,
+    ) -> bool {
+        choice
+            .conditions
+            .iter()
+            .all(|condition| condition.holds(meowney, progress, jobs, inventory, items))
+            && self.gifts_fit(choice, inventory, items)
+    }
+
+    /// Whether the items given by a choice and the node it leads to fit in the inventory.
+    fn gifts_fit(&self, choice: &Choice, inventory: &Inventory, items: &ItemCatalog) -> bool {
+        let next_effects = choice
+            .next
+            .as_ref()
+            .and_then(|next| self.nodes.get(next))
+            .map_or(&[][..], |node| &node.effects);
+        choice
+            .effects
+            .iter()
+            .chain(next_effects)
+            .all(|effect| match effect {
+                Effect::GiveItem(item, count) => inventory.can_add(items, item, *count),
+                _ => true,
+            })
+    }
+
+    fn validate_references(&self, jobs: &JobBoard, items: &ItemCatalog) -> anyhow::Result<()> {
+        for (name, node) in &self.nodes {
+            let conditions = node.choices.iter().flat_map(|choice| &choice.conditions);
+            let effects = node
+                .effects
+                .iter()
+                .chain(node.choices.iter().flat_map(|choice| &choice.effects));
+            for reference in conditions
+                .filter_map(Condition::reference)
+                .chain(effects.filter_map(Effect::reference))
+            {
+                reference
+                    .check(jobs, items)
+                    .map_err(|error| error.context(format!("in node {name:?}")))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Every conversation in town, by name.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "0d6e4a53-9b1f-4c8e-a7d2-3f5b61c9e08a"]
+pub struct Dialogues {
+    pub trees: HashMap<String, DialogueTree>,
+}
+
+#[derive(Default)]
+pub struct DialoguesLoader;
+
+impl AssetLoader for DialoguesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let dialogues: Dialogues = ron::de::from_bytes(bytes)?;
+            for (name, tree) in &dialogues.trees {
+                tree.validate()
+                    .map_err(|error| error.context(format!("invalid dialogue tree {name:?}")))?;
+            }
+            load_context.set_default_asset(LoadedAsset::new(dialogues));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["dialogue"]
+    }
+}
+
+/// Checks the jobs and items named in dialogue exist, once the town data has loaded.
+#[instrument(skip(town_data, dialogues, boards, catalogs))]
+pub fn validate_references(
+    town_data: Res<TownData>,
+    dialogues: Res<Assets<Dialogues>>,
+    boards: Res<Assets<JobBoard>>,
+    catalogs: Res<Assets<ItemCatalog>>,
+) {
+    let (dialogues, board, catalog) = match (
+        dialogues.get(&town_data.dialogue),
+        boards.get(&town_data.jobs),
+        catalogs.get(&town_data.items),
+    ) {
+        (Some(dialogues), Some(board), Some(catalog)) => (dialogues, board, catalog),
+        _ => {
+            warn!("town data not loaded, can't check dialogue");
+            return;
+        }
+    };
+
+    for (name, tree) in &dialogues.trees {
+        if let Err(error) = tree.validate_references(board, catalog) {
+            error!(tree = %name, ?error, "invalid dialogue tree");
+        }
+    }
+}
+
+/// The conversation the player is in.
+#[derive(Clone, Debug)]
+pub struct Conversation {
+    /// Name of the character being talked to.
+    pub speaker: String,
+    pub tree: String,
+    pub node: String,
+}
+
+#[derive(Component)]
+pub struct DialogueBox;
+
+/// Button for the choice at this index of the current node.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct ChoiceButton(usize);
+
+/// Starts talking to the character the player is standing next to.
+#[instrument(skip(commands, interact, nearby_door, nearby_npc, npcs, town, target))]
+pub fn start_conversation(
+    mut commands: Commands,
+    interact: Interact,
+    nearby_door: Res<NearbyDoor>,
+    nearby_npc: Res<NearbyNpc>,
+    npcs: Query<&Npc>,
+    town: Town,
+    mut target: EffectTarget,
+) {
+    // Doors take priority over anyone standing near them
+    if !interact.just_pressed() || nearby_door.0.is_some() {
+        return;
+    }
+
+    let npc = match nearby_npc.0.and_then(|entity| npcs.get(entity).ok()) {
+        Some(npc) => npc,
+        None => return,
+    };
+    let tree_name = match &npc.dialogue {
+        Some(tree_name) => tree_name,
+        None => return,
+    };
+    let tree = match town
+        .dialogues()
+        .and_then(|dialogues| dialogues.trees.get(tree_name))
+    {
+        Some(tree) => tree,
+        None => {
+            warn!(npc = %npc.id, tree = %tree_name, "dialogue tree not found");
+            return;
+        }
+    };
+    let items = match town.items() {
+        Some(items) => items,
+        None => return,
+    };
+
+    info!(npc = %npc.id, tree = %tree_name, "starting conversation");
+    for effect in &tree.nodes[&tree.start].effects {
+        effect.apply(&mut commands, &mut target, items);
+    }
+    commands.insert_resource(Conversation {
+        speaker: npc.name.clone(),
+        tree: tree_name.clone(),
+        node: tree.start.clone(),
+    });
+    commands.insert_resource(InputSuspended);
+}
+
+/// Redraws the dialogue box when the conversation or what the player has changes.
+#[instrument(skip(commands, conversation, boxes, town, fonts, data))]
+pub fn show_conversation(
+    mut commands: Commands,
+    conversation: Option<Res<Conversation>>,
+    boxes: Query<Entity, With<DialogueBox>>,
+    town: Town,
+    fonts: Res<Fonts>,
+    data: SaveData,
+) {
+    let conversation = match conversation {
+        Some(conversation) => conversation,
+        None => {
+            boxes.for_each(|entity| commands.entity(entity).despawn_recursive());
+            return;
+        }
+    };
+    if !conversation.is_changed() && !data.is_changed() {
+        return;
+    }
+    boxes.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let (tree, node) = match town
+        .dialogues()
+        .and_then(|dialogues| dialogues.trees.get(&conversation.tree))
+        .and_then(|tree| Some((tree, tree.nodes.get(&conversation.node)?)))
+    {
+        Some((tree, node)) => (tree, node),
+        None => return,
+    };
+    let items = match town.items() {
+        Some(items) => items,
+        None => return,
+    };
+
+    let text_style = |color| TextStyle {
+        font: fonts.vt323.clone(),
+        font_size: 36.,
+        color,
+    };
+    let choices = if node.choices.is_empty() {
+        vec![("Goodbye".to_string(), true)]
+    } else {
+        node.choices
+            .iter()
+            .map(|choice| {
+                let available = tree.can_pick(
+                    choice,
+                    &data.meowney,
+                    &data.progress,
+                    &data.jobs,
+                    &data.inventory,
+                    items,
+                );
+                (choice.text.clone(), available)
+            })
+            .collect()
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(10.),
+                    bottom: Val::Px(24.),
+                    ..Default::default()
+                },
+                size: Size {
+                    width: Val::Percent(80.),
+                    height: Val::Auto,
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::FlexStart,
+                padding: Rect::all(Val::Px(16.)),
+                ..Default::default()
+            },
+            color: OVERLAY_PANEL_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(Name::new("Dialogue Box"))
+        .insert(DialogueBox)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                text: Text::with_section(
+                    conversation.speaker.clone(),
+                    text_style(HIGHLIGHT_COLOR),
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        top: Val::Px(8.),
+                        bottom: Val::Px(8.),
+                        ..Default::default()
+                    },
+                    max_size: Size {
+                        width: Val::Px(1100.),
+                        height: Val::Undefined,
+                    },
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    node.text.clone(),
+                    text_style(Color::WHITE),
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            for (index, (text, available)) in choices.into_iter().enumerate() {
+                spawn_choice_button(
+                    parent,
+                    index,
+                    format!("{}. {text}", index + 1),
+                    text_style(Color::WHITE),
+                    available,
+                    ChoiceButton(index),
+                );
+            }
+        });
+}
+
+/// Picks the activated choice, applying its effects and moving the conversation on.
+#[instrument(skip(commands, activated, buttons, conversation, town, target))]
+pub fn choose(
+    mut commands: Commands,
+    mut activated: EventReader<Activated>,
+    buttons: Query<&ChoiceButton>,
+    conversation: Option<ResMut<Conversation>>,
+    town: Town,
+    mut target: EffectTarget,
+) {
+    let mut conversation = match conversation {
+        Some(conversation) => conversation,
+        None => return,
+    };
+    let tree = match town
+        .dialogues()
+        .and_then(|dialogues| dialogues.trees.get(&conversation.tree))
+    {
+        Some(tree) => tree,
+        None => return,
+    };
+    let items = match town.items() {
+        Some(items) => items,
+        None => return,
+    };
+
+    for ChoiceButton(index) in activated
+        .iter()
+        .filter_map(|Activated(entity)| buttons.get(*entity).ok())
+    {
+        let choice = tree.nodes[&conversation.node].choices.get(*index);
+        // The game can change between drawing the choice and picking it
+        if let Some(choice) = choice {
+            let available = tree.can_pick(
+                choice,
+                &target.meowney,
+                &target.progress,
+                &target.jobs,
+                &target.inventory,
+                items,
+            );
+            if !available {
+                debug!(choice = %choice.text, "choice can no longer be picked");
+                continue;
+            }
+        }
+
+        // Ended first so input stays suspended if an effect opens the ticket booth
+        let next = choice.and_then(|choice| choice.next.as_ref());
+        if next.is_none() {
+            info!("conversation over");
+            end(&mut commands);
+        }
+
+        for effect in choice.iter().flat_map(|choice| &choice.effects) {
+            effect.apply(&mut commands, &mut target, items);
+        }
+        if let Some(next) = next {
+            debug!(node = %next, "conversation moved on");
+            for effect in &tree.nodes[next].effects {
+                effect.apply(&mut commands, &mut target, items);
+            }
+            conversation.node = next.clone();
+        }
+
+        return;
+    }
+}
+
+#[instrument(skip(commands, boxes))]
+pub fn teardown(mut commands: Commands, boxes: Query<Entity, With<DialogueBox>>) {
+    boxes.for_each(|entity| commands.entity(entity).despawn_recursive());
+    end(&mut commands);
+}
+
+fn end(commands: &mut Commands) {
+    commands.remove_resource::<Conversation>();
+    commands.remove_resource::<InputSuspended>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn town_dialogue_refers_to_real_jobs_and_items() {
+        let dialogues: Dialogues =
+            ron::de::from_str(include_str!("../assets/data/town.dialogue")).unwrap();
+        let jobs: JobBoard = ron::de::from_str(include_str!("../assets/data/town.jobs")).unwrap();
+        let items: ItemCatalog =
+            ron::de::from_str(include_str!("../assets/data/town.items")).unwrap();
+
+        for (name, tree) in &dialogues.trees {
+            if let Err(error) = tree
+                .validate()
+                .and_then(|_| tree.validate_references(&jobs, &items))
+            {
+                panic!("invalid dialogue tree {name:?}: {error:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_jobs_and_items_are_reported() {
+        let jobs = JobBoard { jobs: Vec::new() };
+        let items = ItemCatalog { items: Vec::new() };
+        let tree = |choice: &str| -> DialogueTree {
+            ron::de::from_str(&format!(
+                "(start: \"start\", nodes: {{\"start\": (text: \"Howdy\", choices: [{choice}])}})"
+            ))
+            .unwrap()
+        };
+
+        assert!(tree("(text: \"Sure\")")
+            .validate_references(&jobs, &items)
+            .is_ok());
+        for choice in [
+            "(text: \"Sure\", effects: [StartJob(\"missing\")])",
+            "(text: \"Sure\", effects: [GiveItem(\"missing\", 1)])",
+            "(text: \"Sure\", conditions: [JobCompleted(\"missing\")])",
+            "(text: \"Sure\", conditions: [HasRoomFor(\"missing\")])",
+        ] {
+            assert!(tree(choice).validate_references(&jobs, &items).is_err());
+        }
+    }
+}
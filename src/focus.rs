@@ -0,0 +1,171 @@
+//! Picking buttons with the keyboard or a gamepad as well as the mouse.
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{action::Action, replay::Replayer, ui::HIGHLIGHT_COLOR};
+
+/// A button that can be focused and picked, visited in order of the number given.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Focusable(pub usize);
+
+/// Marks the focused button.
+#[derive(Component, Debug)]
+pub struct Focused;
+
+/// Number of overlays open on top of a focusable button.
+#[derive(Component, Debug)]
+pub struct Covered(u32);
+
+/// Color a button's text had before it was focused.
+#[derive(Component, Debug)]
+pub struct UnfocusedColor(Color);
+
+/// Sent when a focusable button is picked.
+#[derive(Clone, Copy, Debug)]
+pub struct Activated(pub Entity);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Pointed {
+    pub button: usize,
+    pub clicked: bool,
+}
+
+/// The focusable button the mouse moved onto or clicked this frame, if any.
+#[derive(Default, Debug)]
+pub struct Pointer(pub Option<Pointed>);
+
+pub fn read_pointer(
+    buttons: Query<(&Focusable, &Interaction), (Changed<Interaction>, Without<Covered>)>,
+    replayer: Option<Res<Replayer>>,
+    mut pointer: ResMut<Pointer>,
+) {
+    if replayer.is_some() {
+        return;
+    }
+
+    let pointed = buttons
+        .iter()
+        .find(|(_, interaction)| **interaction != Interaction::None)
+        .map(|(focusable, interaction)| Pointed {
+            button: focusable.0,
+            clicked: *interaction == Interaction::Clicked,
+        });
+    if pointer.0 != pointed {
+        pointer.0 = pointed;
+    }
+}
+
+/// Moves focus between buttons and activates the focused one.
+#[instrument(skip(commands, actions, pointer, buttons, activated))]
+pub fn navigate(
+    mut commands: Commands,
+    actions: Res<Input<Action>>,
+    pointer: Res<Pointer>,
+    buttons: Query<(Entity, &Focusable, Option<&Focused>), Without<Covered>>,
+    mut activated: EventWriter<Activated>,
+) {
+    let mut order = buttons
+        .iter()
+        .map(|(entity, focusable, focused)| (*focusable, entity, focused.is_some()))
+        .collect::<Vec<_>>();
+    if order.is_empty() {
+        return;
+    }
+    order.sort_unstable_by_key(|(focusable, ..)| *focusable);
+
+    let focused = order.iter().position(|(.., focused)| *focused);
+    let mut target = focused.unwrap_or(0);
+    let mut activate = false;
+
+    let pointed = pointer.0.and_then(|pointed| {
+        let index = order
+            .iter()
+            .position(|(focusable, ..)| focusable.0 == pointed.button)?;
+        Some((index, pointed.clicked))
+    });
+    if let Some((index, clicked)) = pointed {
+        target = index;
+        activate = clicked;
+    } else if actions.just_pressed(Action::MoveUp) {
+        target = (target + order.len() - 1) % order.len();
+    } else if actions.just_pressed(Action::MoveDown) {
+        target = (target + 1) % order.len();
+    } else if actions.just_pressed(Action::Interact) && focused.is_some() {
+        activate = true;
+    }
+
+    if focused != Some(target) {
+        if let Some(index) = focused {
+            commands.entity(order[index].1).remove::<Focused>();
+        }
+        commands.entity(order[target].1).insert(Focused);
+    }
+    if activate {
+        debug!(button = ?order[target].0, "button activated");
+        activated.send(Activated(order[target].1));
+    }
+}
+
+/// Covers the buttons already on screen when an overlay opens.
+pub fn cover(
+    mut commands: Commands,
+    mut buttons: Query<(Entity, Option<&mut Covered>), With<Focusable>>,
+) {
+    for (entity, covered) in buttons.iter_mut() {
+        match covered {
+            Some(mut covered) => covered.0 += 1,
+            None => {
+                commands.entity(entity).insert(Covered(1));
+            }
+        }
+    }
+}
+
+pub fn uncover(mut commands: Commands, mut buttons: Query<(Entity, &mut Covered)>) {
+    for (entity, mut covered) in buttons.iter_mut() {
+        covered.0 -= 1;
+        if covered.0 == 0 {
+            commands.entity(entity).remove::<Covered>();
+        }
+    }
+}
+
+pub fn highlight(
+    mut commands: Commands,
+    buttons: Query<(&Children, Option<&Focused>), With<Focusable>>,
+    mut texts: Query<(Entity, &mut Text, Option<&UnfocusedColor>)>,
+) {
+    for (children, focused) in buttons.iter() {
+        for child in children.iter() {
+            let (entity, mut text, unfocused) = match texts.get_mut(*child) {
+                Ok(text) => text,
+                Err(_) => continue,
+            };
+            // Remember the color first, and start highlighting once that's been done
+            let unfocused = match unfocused {
+                Some(UnfocusedColor(color)) => *color,
+                None => {
+                    let color = text
+                        .sections
+                        .first()
+                        .map_or(Color::WHITE, |section| section.style.color);
+                    commands.entity(entity).insert(UnfocusedColor(color));
+                    continue;
+                }
+            };
+
+            let color = if focused.is_some() {
+                HIGHLIGHT_COLOR
+            } else {
+                unfocused
+            };
+            for section in &mut text.sections {
+                if section.style.color != color {
+                    section.style.color = color;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,243 @@
+//! Best post office runs, kept in the data directory.
+
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    scene::post_office::{difficulty::Difficulty, snake::ArenaMode},
+};
+
+const HIGH_SCORES_FILE_NAME: &str = "highscores.ron";
+
+/// Number of runs kept in the table for each kind of arena.
+pub const MAX_HIGH_SCORES: usize = 10;
+
+/// The kind of arena a run was played in. Runs are only ranked against others in the same kind
+/// of arena.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArenaKind {
+    pub mode: ArenaMode,
+    /// Name of the arena layout, empty for runs recorded before layouts were.
+    pub layout: String,
+}
+
+impl ArenaKind {
+    pub fn name(&self) -> String {
+        let layout = if self.layout.is_empty() {
+            "Unknown layout"
+        } else {
+            &self.layout
+        };
+        format!("{}, {layout}", self.mode.name())
+    }
+}
+
+/// A finished game of snake.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct HighScore {
+    /// Meowney earned.
+    pub score: u32,
+    /// Length of the snake at the end of the game.
+    pub length: usize,
+    pub difficulty: Difficulty,
+    /// Added after the first tables were written, which were all played in walled arenas.
+    #[serde(default)]
+    pub arena: ArenaKind,
+    /// Seed of the run's food placement.
+    pub seed: u64,
+    /// Day the game was played, as `YYYY-MM-DD` in UTC.
+    pub date: String,
+}
+
+/// The best runs in each kind of arena, highest score first.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct HighScores {
+    pub entries: Vec<HighScore>,
+}
+
+impl HighScores {
+    fn path() -> Option<PathBuf> {
+        config::data_dir().map(|dir| dir.join(HIGH_SCORES_FILE_NAME))
+    }
+
+    /// Loads the table, starting a new one if there isn't one yet or it can't be read.
+    pub fn load() -> Self {
+        let path = match Self::path() {
+            Some(path) if path.exists() => path,
+            _ => return Self::default(),
+        };
+
+        match config::read(&path) {
+            Ok(high_scores) => high_scores,
+            Err(error) => {
+                warn!(?error, path = %path.display(), "starting a new high score table");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let path = Self::path().context("no data directory")?;
+        config::write(&path, self)
+    }
+
+    /// Runs played in one kind of arena, highest score first.
+    pub fn for_arena<'a>(&'a self, arena: &'a ArenaKind) -> impl Iterator<Item = &'a HighScore> {
+        self.entries
+            .iter()
+            .filter(move |entry| entry.arena == *arena)
+    }
+
+    /// Every kind of arena with runs in the table, in the order of their best runs.
+    pub fn arenas(&self) -> Vec<&ArenaKind> {
+        let mut arenas = Vec::<&ArenaKind>::new();
+        for entry in &self.entries {
+            if !arenas.contains(&&entry.arena) {
+                arenas.push(&entry.arena);
+            }
+        }
+        arenas
+    }
+
+    /// Adds a run to the table and returns its rank among runs in the same kind of arena,
+    /// counting from 0, or `None` if it didn't make the table.
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        // Ties go below existing entries, so a run has to beat a score to take its place
+        let beats = |existing: &HighScore| entry.score > existing.score;
+        let rank = self
+            .for_arena(&entry.arena)
+            .position(beats)
+            .unwrap_or_else(|| self.for_arena(&entry.arena).count());
+        if rank >= MAX_HIGH_SCORES {
+            return None;
+        }
+
+        let index = self
+            .entries
+            .iter()
+            .position(beats)
+            .unwrap_or(self.entries.len());
+        let arena = entry.arena.clone();
+        self.entries.insert(index, entry);
+
+        let mut kept = 0;
+        self.entries.retain(|existing| {
+            if existing.arena != arena {
+                return true;
+            }
+            kept += 1;
+            kept <= MAX_HIGH_SCORES
+        });
+        Some(rank)
+    }
+}
+
+/// Today's date as `YYYY-MM-DD` in UTC.
+pub fn today() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+/// Converts days since 1970-01-01 to a year, month and day in the proleptic Gregorian calendar.
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html#civil_from_days>.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_days_since_the_epoch_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(19_782), (2024, 2, 29));
+    }
+
+    #[test]
+    fn handles_leap_years() {
+        // Divisible by 400, so a leap year
+        assert_eq!(civil_from_days(11_016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11_017), (2000, 3, 1));
+        // Divisible by 100 but not 400, so not leap years
+        assert_eq!(civil_from_days(-25_509), (1900, 2, 28));
+        assert_eq!(civil_from_days(-25_508), (1900, 3, 1));
+        assert_eq!(civil_from_days(47_540), (2100, 2, 28));
+        assert_eq!(civil_from_days(47_541), (2100, 3, 1));
+    }
+
+    fn run(score: u32, mode: ArenaMode) -> HighScore {
+        HighScore {
+            score,
+            length: 3,
+            difficulty: Difficulty::Normal,
+            arena: ArenaKind {
+                mode,
+                layout: "Empty floor".to_string(),
+            },
+            seed: 0,
+            date: "2024-02-29".to_string(),
+        }
+    }
+
+    #[test]
+    fn ranks_runs_against_the_same_kind_of_arena() {
+        let mut high_scores = HighScores::default();
+        assert_eq!(high_scores.insert(run(10, ArenaMode::Walled)), Some(0));
+        assert_eq!(high_scores.insert(run(20, ArenaMode::Wrap)), Some(0));
+        assert_eq!(high_scores.insert(run(5, ArenaMode::Wrap)), Some(1));
+        assert_eq!(high_scores.insert(run(10, ArenaMode::Walled)), Some(1));
+
+        let wrap = run(0, ArenaMode::Wrap).arena;
+        let scores = high_scores
+            .for_arena(&wrap)
+            .map(|entry| entry.score)
+            .collect::<Vec<_>>();
+        assert_eq!(scores, [20, 5]);
+        assert_eq!(high_scores.arenas().len(), 2);
+    }
+
+    #[test]
+    fn keeps_the_best_runs_for_each_kind_of_arena() {
+        let mut high_scores = HighScores::default();
+        for score in 1..=MAX_HIGH_SCORES as u32 {
+            high_scores.insert(run(score, ArenaMode::Walled));
+        }
+        high_scores.insert(run(1, ArenaMode::Fenced));
+
+        assert_eq!(high_scores.insert(run(0, ArenaMode::Walled)), None);
+        assert_eq!(high_scores.insert(run(100, ArenaMode::Walled)), Some(0));
+
+        let walled = run(0, ArenaMode::Walled).arena;
+        assert_eq!(high_scores.for_arena(&walled).count(), MAX_HIGH_SCORES);
+        assert_eq!(high_scores.for_arena(&walled).last().unwrap().score, 2);
+        assert_eq!(high_scores.entries.len(), MAX_HIGH_SCORES + 1);
+    }
+}
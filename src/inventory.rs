@@ -0,0 +1,345 @@
+//! Things the player is carrying besides Meowney, and the items defined in
+//! `assets/data/town.items`.
+
+use std::{collections::BTreeMap, path::PathBuf};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    action::Action, assets::Fonts, scene::outside::npc::TownData, ui::OVERLAY_PANEL_COLOR,
+};
+
+/// Size, in pixels, item icons are drawn at in the inventory panel.
+const ICON_SIZE: f32 = 48.;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ItemDefinition {
+    pub id: String,
+    pub name: String,
+    /// Path of the icon image, relative to `assets`.
+    pub icon: String,
+    /// The loaded icon, set by [`ItemCatalogLoader`].
+    #[serde(skip)]
+    pub texture: Handle<Image>,
+    /// Most of this item the player can carry at once.
+    #[serde(default = "default_stack_size")]
+    pub stack_size: u32,
+    #[serde(default)]
+    pub description: String,
+}
+
+fn default_stack_size() -> u32 {
+    1
+}
+
+/// Every item in the game.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "7f3a9c15-d2e8-4b60-8e4f-1a6b0c93d527"]
+pub struct ItemCatalog {
+    pub items: Vec<ItemDefinition>,
+}
+
+impl ItemCatalog {
+    pub fn get(&self, id: &str) -> Option<&ItemDefinition> {
+        self.items.iter().find(|item| item.id == id)
+    }
+}
+
+#[derive(Default)]
+pub struct ItemCatalogLoader;
+
+impl AssetLoader for ItemCatalogLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut catalog: ItemCatalog = ron::de::from_bytes(bytes)?;
+            let mut icons = Vec::new();
+            for item in &mut catalog.items {
+                let path = AssetPath::new(PathBuf::from(&item.icon), None);
+                item.texture = load_context.get_handle(path.clone());
+                icons.push(path);
+            }
+            load_context.set_default_asset(LoadedAsset::new(catalog).with_dependencies(icons));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["items"]
+    }
+}
+
+/// How many of each item the player is carrying, by item id.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Inventory {
+    pub items: BTreeMap<String, u32>,
+}
+
+impl Inventory {
+    pub fn count(&self, id: &str) -> u32 {
+        self.items.get(id).copied().unwrap_or_default()
+    }
+
+    pub fn has(&self, id: &str) -> bool {
+        self.count(id) > 0
+    }
+
+    /// Items carried and how many of each, in id order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, u32)> {
+        self.items.iter().map(|(id, count)| (id.as_str(), *count))
+    }
+
+    /// Whether `count` more of an item fit in its stack. Items missing from the catalog never fit.
+    pub fn can_add(&self, catalog: &ItemCatalog, id: &str, count: u32) -> bool {
+        catalog
+            .get(id)
+            .map_or(false, |item| self.count(id) + count <= item.stack_size)
+    }
+
+    /// Adds `count` of an item, or nothing if they don't all fit in its stack. Returns whether the
+    /// items were added.
+    pub fn add(&mut self, catalog: &ItemCatalog, id: &str, count: u32) -> bool {
+        if catalog.get(id).is_none() {
+            warn!(item = %id, "tried to add an item that isn't in the catalog");
+            return false;
+        }
+        if !self.can_add(catalog, id, count) {
+            warn!(item = %id, count, "no room for item");
+            return false;
+        }
+
+        info!(item = %id, count, "item added");
+        *self.items.entry(id.to_string()).or_default() += count;
+        true
+    }
+
+    /// Whether all of a list of items and counts fit, e.g. a job's reward.
+    pub fn can_add_all(&self, catalog: &ItemCatalog, items: &[(String, u32)]) -> bool {
+        items
+            .iter()
+            .all(|(id, count)| self.can_add(catalog, id, *count))
+    }
+
+    /// Takes `count` of an item, or nothing if the player doesn't have that many. Returns whether
+    /// the items were taken.
+    pub fn remove(&mut self, id: &str, count: u32) -> bool {
+        let held = self.count(id);
+        if held < count {
+            return false;
+        }
+
+        info!(item = %id, count, "item removed");
+        if held == count {
+            self.items.remove(id);
+        } else {
+            self.items.insert(id.to_string(), held - count);
+        }
+        true
+    }
+}
+
+#[derive(Component)]
+pub struct InventoryPanel;
+
+/// Whether the inventory panel is open.
+#[derive(Debug, Default)]
+pub struct InventoryOpen(pub bool);
+
+/// Opens or closes the inventory panel when the inventory action is pressed.
+#[instrument(skip(actions, open))]
+pub fn toggle_panel(actions: Res<Input<Action>>, mut open: ResMut<InventoryOpen>) {
+    if actions.just_pressed(Action::Inventory) {
+        open.0 = !open.0;
+        info!(open = open.0, "toggled inventory");
+    }
+}
+
+/// Redraws the inventory panel when it opens or closes, and whenever the inventory changes.
+pub fn update_panel(
+    mut commands: Commands,
+    open: Res<InventoryOpen>,
+    inventory: Res<Inventory>,
+    town_data: Res<TownData>,
+    catalogs: Res<Assets<ItemCatalog>>,
+    fonts: Res<Fonts>,
+    panels: Query<Entity, With<InventoryPanel>>,
+) {
+    if !open.is_changed() && !inventory.is_changed() {
+        return;
+    }
+    panels.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let catalog = match catalogs.get(&town_data.items) {
+        Some(catalog) if open.0 => catalog,
+        _ => return,
+    };
+
+    let text_style = |font_size, color| TextStyle {
+        font: fonts.vt323.clone(),
+        font_size,
+        color,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    right: Val::Px(16.),
+                    top: Val::Px(64.),
+                    ..Default::default()
+                },
+                size: Size {
+                    width: Val::Px(480.),
+                    height: Val::Auto,
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(12.)),
+                ..Default::default()
+            },
+            color: OVERLAY_PANEL_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(Name::new("Inventory Panel"))
+        .insert(InventoryPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect {
+                        bottom: Val::Px(8.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "Inventory",
+                    text_style(40., Color::WHITE),
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            if inventory.items.is_empty() {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "Nothing but lint",
+                        text_style(28., Color::WHITE),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+
+            for (id, count) in inventory.iter() {
+                let item = match catalog.get(id) {
+                    Some(item) => item,
+                    None => {
+                        warn!(item = %id, "carrying an item that isn't in the catalog");
+                        continue;
+                    }
+                };
+
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            margin: Rect::all(Val::Px(4.)),
+                            ..Default::default()
+                        },
+                        color: Color::NONE.into(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn_bundle(ImageBundle {
+                            style: Style {
+                                size: Size::new(Val::Px(ICON_SIZE), Val::Px(ICON_SIZE)),
+                                margin: Rect {
+                                    right: Val::Px(8.),
+                                    ..Default::default()
+                                },
+                                ..Default::default()
+                            },
+                            image: item.texture.clone().into(),
+                            ..Default::default()
+                        });
+                        parent
+                            .spawn_bundle(NodeBundle {
+                                style: Style {
+                                    flex_direction: FlexDirection::ColumnReverse,
+                                    ..Default::default()
+                                },
+                                color: Color::NONE.into(),
+                                ..Default::default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        format!("{} x{count}", item.name),
+                                        text_style(28., Color::WHITE),
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                                parent.spawn_bundle(TextBundle {
+                                    style: Style {
+                                        max_size: Size {
+                                            width: Val::Px(400.),
+                                            height: Val::Undefined,
+                                        },
+                                        ..Default::default()
+                                    },
+                                    text: Text::with_section(
+                                        item.description.clone(),
+                                        text_style(22., Color::rgb(0.85, 0.85, 0.85)),
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            });
+                    });
+            }
+        });
+}
+
+#[instrument(skip(commands, open, panels))]
+pub fn teardown_panel(
+    mut commands: Commands,
+    mut open: ResMut<InventoryOpen>,
+    panels: Query<Entity, With<InventoryPanel>>,
+) {
+    panels.for_each(|entity| commands.entity(entity).despawn_recursive());
+    open.0 = false;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn items_are_only_added_when_they_all_fit() {
+        let catalog: ItemCatalog = ron::de::from_str(
+            r#"(items: [(id: "stamp", name: "Stamp", icon: "images/stamp.png", stack_size: 3)])"#,
+        )
+        .unwrap();
+        let mut inventory = Inventory::default();
+
+        assert!(inventory.add(&catalog, "stamp", 2));
+        assert!(!inventory.add(&catalog, "stamp", 2));
+        assert_eq!(inventory.count("stamp"), 2);
+        assert!(inventory.add(&catalog, "stamp", 1));
+        assert_eq!(inventory.count("stamp"), 3);
+        assert!(!inventory.add(&catalog, "postcard", 1));
+        assert!(!inventory.can_add_all(&catalog, &[("stamp".to_string(), 1)]));
+    }
+}
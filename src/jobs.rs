@@ -0,0 +1,474 @@
+//! Odd jobs around town, defined in `assets/data/town.jobs`, and how far the player is through
+//! them.
+
+use std::collections::BTreeMap;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    assets::Fonts,
+    inventory::{Inventory, ItemCatalog},
+    scene::{outside::npc::TownData, post_office::results::SnakeResults},
+    Meowney,
+};
+
+/// Something the player has to do to finish a job.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Objective {
+    /// Finish this many post office shifts.
+    WorkShifts(u32),
+    DeliverLetters(u32),
+    DeliverParcels(u32),
+    DeliverExpress(u32),
+    /// Earn at least this much Meowney in a single shift.
+    EarnInOneShift(u32),
+    /// Fill the whole sorting room in a single shift.
+    ClearBoard,
+}
+
+impl Objective {
+    /// Progress needed for the objective to be met.
+    pub fn target(self) -> u32 {
+        match self {
+            Objective::WorkShifts(count)
+            | Objective::DeliverLetters(count)
+            | Objective::DeliverParcels(count)
+            | Objective::DeliverExpress(count) => count,
+            Objective::EarnInOneShift(_) | Objective::ClearBoard => 1,
+        }
+    }
+
+    /// Progress made towards the objective by a finished shift.
+    fn progress_from(self, shift: &SnakeResults) -> u32 {
+        match self {
+            Objective::WorkShifts(_) => 1,
+            Objective::DeliverLetters(_) => shift.letters,
+            Objective::DeliverParcels(_) => shift.parcels,
+            Objective::DeliverExpress(_) => shift.express,
+            Objective::EarnInOneShift(amount) => u32::from(shift.earned >= amount),
+            Objective::ClearBoard => u32::from(shift.game_over.is_win()),
+        }
+    }
+
+    fn describe(self) -> String {
+        match self {
+            Objective::WorkShifts(count) => format!("Work {count} shifts"),
+            Objective::DeliverLetters(count) => format!("Deliver {count} letters"),
+            Objective::DeliverParcels(count) => format!("Deliver {count} parcels"),
+            Objective::DeliverExpress(count) => format!("Deliver {count} express mail"),
+            Objective::EarnInOneShift(amount) => format!("Earn {amount} Meowney in one shift"),
+            Objective::ClearBoard => "Fill the sorting room".to_string(),
+        }
+    }
+}
+
+/// What the player gets for finishing a job.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct Reward {
+    #[serde(default)]
+    pub meowney: u32,
+    /// Ids of items from `assets/data/town.items`, and how many of each.
+    #[serde(default)]
+    pub items: Vec<(String, u32)>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct JobDefinition {
+    pub id: String,
+    pub name: String,
+    pub objectives: Vec<Objective>,
+    #[serde(default)]
+    pub reward: Reward,
+}
+
+impl JobDefinition {
+    /// Whether enough progress has been made towards every objective.
+    fn is_finished(&self, progress: &[u32]) -> bool {
+        self.objectives
+            .iter()
+            .enumerate()
+            .all(|(index, objective)| {
+                progress.get(index).copied().unwrap_or_default() >= objective.target()
+            })
+    }
+}
+
+/// Every job in town.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "c41f7a2e-6b3d-4e95-8a0c-d27e9f1b5a63"]
+pub struct JobBoard {
+    pub jobs: Vec<JobDefinition>,
+}
+
+impl JobBoard {
+    pub fn get(&self, id: &str) -> Option<&JobDefinition> {
+        self.jobs.iter().find(|job| job.id == id)
+    }
+}
+
+#[derive(Default)]
+pub struct JobBoardLoader;
+
+impl AssetLoader for JobBoardLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let board: JobBoard = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(board));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["jobs"]
+    }
+}
+
+/// How far the player has got with a job.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum JobState {
+    /// Not taken on yet.
+    Available,
+    /// Taken on, with the progress made towards each objective.
+    Active {
+        progress: Vec<u32>,
+    },
+    Completed,
+}
+
+/// State of every job the player has taken on. Jobs that aren't listed are available.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Jobs {
+    pub states: BTreeMap<String, JobState>,
+}
+
+impl Jobs {
+    pub fn state(&self, id: &str) -> &JobState {
+        self.states.get(id).unwrap_or(&JobState::Available)
+    }
+
+    pub fn is_available(&self, id: &str) -> bool {
+        *self.state(id) == JobState::Available
+    }
+
+    pub fn is_active(&self, id: &str) -> bool {
+        matches!(self.state(id), JobState::Active { .. })
+    }
+
+    pub fn is_completed(&self, id: &str) -> bool {
+        *self.state(id) == JobState::Completed
+    }
+
+    /// Takes on a job, if it hasn't been already.
+    pub fn start(&mut self, id: &str) {
+        if self.is_available(id) {
+            info!(job = %id, "job started");
+            self.states.insert(
+                id.to_string(),
+                JobState::Active {
+                    progress: Vec::new(),
+                },
+            );
+        }
+    }
+
+    /// Counts a finished shift towards every active job.
+    fn record_shift(&mut self, board: &JobBoard, shift: &SnakeResults) {
+        for (id, state) in &mut self.states {
+            let progress = match state {
+                JobState::Active { progress } => progress,
+                _ => continue,
+            };
+            let job = match board.get(id) {
+                Some(job) => job,
+                None => {
+                    warn!(job = %id, "active job is not on the job board");
+                    continue;
+                }
+            };
+
+            progress.resize(job.objectives.len(), 0);
+            for (objective, done) in job.objectives.iter().zip(progress.iter_mut()) {
+                *done = (*done + objective.progress_from(shift)).min(objective.target());
+            }
+        }
+    }
+
+    /// Active jobs with every objective met, which are done once their reward is paid out.
+    fn finished<'a>(&self, board: &'a JobBoard) -> Vec<&'a JobDefinition> {
+        self.states
+            .iter()
+            .filter_map(|(id, state)| match state {
+                JobState::Active { progress } => {
+                    board.get(id).filter(|job| job.is_finished(progress))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Pays out every finished job whose reward items fit in the inventory, and marks it done.
+    fn pay_out(
+        &mut self,
+        board: &JobBoard,
+        catalog: &ItemCatalog,
+        meowney: &mut Meowney,
+        inventory: &mut Inventory,
+    ) {
+        for job in self.finished(board) {
+            if !inventory.can_add_all(catalog, &job.reward.items) {
+                continue;
+            }
+
+            info!(job = %job.id, reward = ?job.reward, "job completed");
+            meowney.0 += job.reward.meowney;
+            for (item, count) in &job.reward.items {
+                inventory.add(catalog, item, *count);
+            }
+            self.states.insert(job.id.clone(), JobState::Completed);
+        }
+    }
+}
+
+/// Sent when the player finishes a shift at the post office, with how it went.
+#[derive(Clone, Debug)]
+pub struct ShiftFinished(pub SnakeResults);
+
+/// Counts finished shifts towards active jobs and pays out for any that are done. A job whose
+/// reward items don't fit in the inventory stays finished but unpaid until there's room.
+#[instrument(skip(reader, jobs, town_data, boards, catalogs, meowney, inventory))]
+pub fn track_jobs(
+    mut reader: EventReader<ShiftFinished>,
+    mut jobs: ResMut<Jobs>,
+    town_data: Option<Res<TownData>>,
+    boards: Res<Assets<JobBoard>>,
+    catalogs: Res<Assets<ItemCatalog>>,
+    mut meowney: ResMut<Meowney>,
+    mut inventory: ResMut<Inventory>,
+) {
+    // Town data only exists once assets have loaded
+    let town_data = match town_data {
+        Some(town_data) => town_data,
+        None => return,
+    };
+    let (board, catalog) = match (boards.get(&town_data.jobs), catalogs.get(&town_data.items)) {
+        (Some(board), Some(catalog)) => (board, catalog),
+        _ => return,
+    };
+
+    for ShiftFinished(shift) in reader.iter() {
+        jobs.record_shift(board, shift);
+    }
+
+    // Jobs are only borrowed mutably when one is paid out, so they aren't marked changed every frame
+    let payable = jobs
+        .finished(board)
+        .iter()
+        .any(|job| inventory.can_add_all(catalog, &job.reward.items));
+    if payable {
+        jobs.pay_out(board, catalog, &mut meowney, &mut inventory);
+    }
+}
+
+#[derive(Component)]
+pub struct JobTracker;
+
+#[instrument(skip(commands, fonts))]
+pub fn setup_tracker(mut commands: Commands, fonts: Res<Fonts>) {
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(16.),
+                    left: Val::Px(16.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.vt323.clone(),
+                    font_size: 32.0,
+                    color: Color::WHITE,
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(Name::new("Job Tracker"))
+        .insert(JobTracker);
+}
+
+/// Lists active jobs and their objectives in the corner of the screen.
+pub fn update_tracker(
+    jobs: Res<Jobs>,
+    town_data: Res<TownData>,
+    boards: Res<Assets<JobBoard>>,
+    mut trackers: Query<(&mut Text, ChangeTrackers<JobTracker>)>,
+) {
+    let stale = jobs.is_changed() || trackers.iter().any(|(_, tracker)| tracker.is_added());
+    let board = match boards.get(&town_data.jobs) {
+        Some(board) if stale => board,
+        _ => return,
+    };
+
+    let mut lines = Vec::new();
+    for (id, state) in &jobs.states {
+        let (job, progress) = match (board.get(id), state) {
+            (Some(job), JobState::Active { progress }) => (job, progress),
+            _ => continue,
+        };
+
+        if job.is_finished(progress) {
+            lines.push(format!("{} (make room for the reward)", job.name));
+            continue;
+        }
+        lines.push(job.name.clone());
+        for (index, objective) in job.objectives.iter().enumerate() {
+            let done = progress.get(index).copied().unwrap_or_default();
+            lines.push(format!(
+                "  {} ({done}/{})",
+                objective.describe(),
+                objective.target()
+            ));
+        }
+    }
+    let value = lines.join("\n");
+
+    for (mut text, _) in trackers.iter_mut() {
+        text.sections[0].value = value.clone();
+    }
+}
+
+#[instrument(skip(commands, query))]
+pub fn teardown_tracker(mut commands: Commands, query: Query<Entity, With<JobTracker>>) {
+    query.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{high_scores::ArenaKind, scene::post_office::snake::GameOver};
+
+    fn board() -> JobBoard {
+        ron::de::from_str(
+            r#"(jobs: [
+                (
+                    id: "sorting",
+                    name: "Sorting",
+                    objectives: [WorkShifts(2), DeliverLetters(5)],
+                    reward: (meowney: 30, items: [("stamp", 1)]),
+                ),
+                (id: "tidy_up", name: "Tidy up", objectives: [ClearBoard]),
+            ])"#,
+        )
+        .unwrap()
+    }
+
+    fn catalog() -> ItemCatalog {
+        ron::de::from_str(r#"(items: [(id: "stamp", name: "Stamp", icon: "images/stamp.png")])"#)
+            .unwrap()
+    }
+
+    fn shift(letters: u32) -> SnakeResults {
+        SnakeResults {
+            game_over: GameOver::HitWall,
+            letters,
+            parcels: 0,
+            express: 0,
+            earned: letters * 10,
+            arena: ArenaKind::default(),
+            rank: None,
+        }
+    }
+
+    #[test]
+    fn counts_shifts_towards_active_jobs() {
+        let board = board();
+        let mut jobs = Jobs::default();
+        jobs.start("sorting");
+
+        jobs.record_shift(&board, &shift(3));
+        assert_eq!(
+            *jobs.state("sorting"),
+            JobState::Active {
+                progress: vec![1, 3]
+            }
+        );
+        // Progress stops at each objective's target
+        jobs.record_shift(&board, &shift(4));
+        assert_eq!(
+            *jobs.state("sorting"),
+            JobState::Active {
+                progress: vec![2, 5]
+            }
+        );
+        assert!(jobs.is_available("tidy_up"));
+    }
+
+    #[test]
+    fn pays_out_finished_jobs() {
+        let (board, catalog) = (board(), catalog());
+        let mut jobs = Jobs::default();
+        let mut meowney = Meowney(5);
+        let mut inventory = Inventory::default();
+        jobs.start("sorting");
+
+        jobs.record_shift(&board, &shift(5));
+        jobs.pay_out(&board, &catalog, &mut meowney, &mut inventory);
+        assert!(jobs.is_active("sorting"));
+        assert_eq!(meowney.0, 5);
+
+        jobs.record_shift(&board, &shift(0));
+        jobs.pay_out(&board, &catalog, &mut meowney, &mut inventory);
+        assert!(jobs.is_completed("sorting"));
+        assert_eq!(meowney.0, 35);
+        assert_eq!(inventory.count("stamp"), 1);
+    }
+
+    #[test]
+    fn holds_rewards_until_there_is_room() {
+        let (board, catalog) = (board(), catalog());
+        let mut jobs = Jobs::default();
+        let mut meowney = Meowney(0);
+        let mut inventory = Inventory::default();
+        inventory.add(&catalog, "stamp", 1);
+        jobs.start("sorting");
+        jobs.record_shift(&board, &shift(5));
+        jobs.record_shift(&board, &shift(0));
+
+        jobs.pay_out(&board, &catalog, &mut meowney, &mut inventory);
+        assert!(jobs.is_active("sorting"));
+        assert_eq!(meowney.0, 0);
+
+        inventory.remove("stamp", 1);
+        jobs.pay_out(&board, &catalog, &mut meowney, &mut inventory);
+        assert!(jobs.is_completed("sorting"));
+        assert_eq!(meowney.0, 30);
+        assert_eq!(inventory.count("stamp"), 1);
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let mut jobs = Jobs::default();
+        jobs.start("sorting");
+        jobs.record_shift(&board(), &shift(3));
+        jobs.states
+            .insert("tidy_up".to_string(), JobState::Completed);
+
+        let loaded: Jobs = ron::de::from_str(&ron::ser::to_string(&jobs).unwrap()).unwrap();
+        assert_eq!(loaded.states, jobs.states);
+    }
+}
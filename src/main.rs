@@ -2,20 +2,58 @@
 
 #![allow(clippy::type_complexity)]
 
+use std::path::PathBuf;
+
+use action::{Action, ActionMap, ConnectedGamepads, LeftStick};
 use animation::Animation;
 use assets::{Fonts, Images, Sprites};
-use bevy::{log::LogSettings, prelude::*};
+use bevy::{input::InputSystem, log::LogSettings, prelude::*, ui::UiSystem};
 use bevy_asset_loader::AssetLoader;
 use bevy_inspector_egui::WorldInspectorPlugin;
 use bevy_rapier2d::prelude::*;
 use clap::Parser;
-use scene::post_office::{LastTailPosition, SnakeGameOver, SnakeGrowth, SnakeSegments, SpawnFood};
+use dialogue::{Dialogues, DialoguesLoader};
+use focus::{Activated, Pointer};
+use high_scores::HighScores;
+use inventory::{Inventory, InventoryOpen, ItemCatalog, ItemCatalogLoader};
+use jobs::{JobBoard, JobBoardLoader, Jobs, ShiftFinished};
+use rand::Rng;
+use replay::{FrameCount, Recorder, Replayer};
+use rng::GameRng;
+use save::{Progress, SaveFile};
+use scene::{
+    high_scores::ShownArena,
+    outside::{
+        npc::{TownData, Townsfolk, TownsfolkLoader},
+        ticket_booth::{Routes, RoutesLoader},
+    },
+    post_office::{
+        difficulty::{CustomDifficulty, SelectedArenaMode, SelectedDifficulty},
+        layout::{ArenaLayout, ArenaLayoutLoader, Layouts, SelectedLayout},
+        SnakeGameOver, SnakeGrowth, SnakeSegments, SpawnFood,
+    },
+};
+use serde::{Deserialize, Serialize};
+use settings::Settings;
 use tracing::instrument;
+use tracing_subscriber::EnvFilter;
 
+mod action;
 mod animation;
 mod assets;
+mod config;
 mod control;
+mod dialogue;
+mod focus;
+mod high_scores;
+mod inventory;
+mod jobs;
+mod replay;
+mod rng;
+mod save;
 mod scene;
+mod settings;
+mod ui;
 
 #[derive(Parser, Debug)]
 #[clap(version, about)]
@@ -26,19 +64,59 @@ struct Args {
     /// Output verbose logs
     #[clap(short, long)]
     verbose: bool,
+    /// Record control input to a file, to be played back with --replay
+    #[clap(
+        long,
+        value_name = "FILE",
+        parse(from_os_str),
+        conflicts_with = "replay"
+    )]
+    record: Option<PathBuf>,
+    /// Play back control input recorded with --record instead of reading the keyboard
+    #[clap(long, value_name = "FILE", parse(from_os_str))]
+    replay: Option<PathBuf>,
+    /// Seed for the random number generator, e.g. to get the same food layout as another run
+    #[clap(long)]
+    seed: Option<u64>,
+    /// Play this many games of snake with the autopilot, without opening a window, and exit
+    #[clap(long, value_name = "RUNS")]
+    soak: Option<u32>,
 }
 
-#[derive(Clone, Eq, PartialEq, Debug, Hash)]
+#[derive(Clone, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
 pub enum GameState {
     AssetLoading,
     MainMenu,
+    HighScores,
     Outside,
+    PostOfficePrompt,
     PostOffice,
+    /// Pushed on top of [`GameState::PostOffice`] when a game of snake ends.
+    PostOfficeResults,
     End,
+    /// Pushed on top of a pausable state while the pause menu is open.
+    Paused,
+    /// Pushed on top of [`GameState::Paused`] while the settings page is open.
+    Settings,
+}
+
+impl GameState {
+    /// Whether the game can be saved, and later continued, in this state.
+    pub fn is_saveable(&self) -> bool {
+        matches!(self, Self::Outside | Self::End)
+    }
+
+    /// Whether the pause menu can be opened in this state.
+    pub fn is_pausable(&self) -> bool {
+        matches!(self, Self::Outside | Self::PostOffice)
+    }
 }
 
 #[derive(SystemLabel, Clone, Debug, PartialEq, Eq, Hash)]
 enum Label {
+    TrackGamepads,
+    MapActions,
+    ReadPointer,
     ReadInput,
     ApplyInput,
     Move,
@@ -52,9 +130,26 @@ enum Label {
 #[derive(Default, Debug)]
 pub struct Meowney(pub u32);
 
-fn main() {
+fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
+    let replayer = args.replay.as_deref().map(Replayer::open).transpose()?;
+    let seed = args
+        .seed
+        .or_else(|| replayer.as_ref().and_then(Replayer::seed))
+        .unwrap_or_else(|| rand::thread_rng().gen());
+
+    let log_level = if args.verbose { "debug" } else { "info" };
+    let filter = format!("meowdy={log_level},wgpu=error,bevy_render=info");
+
+    if let Some(runs) = args.soak {
+        // There's no window or game loop to report on a soak test, so log straight to the terminal
+        let filter = EnvFilter::try_from_default_env().or_else(|_| EnvFilter::try_new(&filter))?;
+        tracing_subscriber::fmt().with_env_filter(filter).init();
+        scene::post_office::autopilot::soak(runs, seed, &CustomDifficulty::load())?;
+        return Ok(());
+    }
+
     let mut app = App::new();
 
     AssetLoader::new(GameState::AssetLoading)
@@ -62,11 +157,10 @@ fn main() {
         .with_collection::<Sprites>()
         .with_collection::<Images>()
         .with_collection::<Fonts>()
+        .with_collection::<Layouts>()
+        .with_collection::<TownData>()
         .build(&mut app);
 
-    let log_level = if args.verbose { "debug" } else { "info" };
-    let filter = format!("meowdy={log_level},wgpu=error,bevy_render=info");
-
     app.insert_resource(WindowDescriptor {
         title: "Meowdy!".into(),
         width: 1440.,
@@ -79,16 +173,78 @@ fn main() {
         ..Default::default()
     })
     .insert_resource(SnakeSegments::default())
-    .insert_resource(LastTailPosition::default())
+    .insert_resource(CustomDifficulty::load())
+    .init_resource::<SelectedDifficulty>()
+    .init_resource::<SelectedArenaMode>()
+    .init_resource::<SelectedLayout>()
     .insert_resource(Meowney::default())
+    .insert_resource(Progress::default())
+    .init_resource::<Jobs>()
+    .init_resource::<Inventory>()
+    .init_resource::<InventoryOpen>()
+    .insert_resource(SaveFile::locate())
+    .insert_resource(HighScores::load())
+    .init_resource::<ShownArena>()
+    .insert_resource(GameRng::from_seed(seed))
+    .insert_resource(ActionMap::load())
+    .insert_resource(Settings::load())
+    .init_resource::<Input<Action>>()
+    .init_resource::<ConnectedGamepads>()
+    .init_resource::<LeftStick>()
+    .init_resource::<Pointer>()
+    .init_resource::<FrameCount>()
     .add_event::<SnakeGrowth>()
     .add_event::<SpawnFood>()
     .add_event::<SnakeGameOver>()
+    .add_event::<ShiftFinished>()
+    .add_event::<Activated>()
     .add_plugins(DefaultPlugins)
+    .add_asset::<ArenaLayout>()
+    .init_asset_loader::<ArenaLayoutLoader>()
+    .add_asset::<Townsfolk>()
+    .init_asset_loader::<TownsfolkLoader>()
+    .add_asset::<Dialogues>()
+    .init_asset_loader::<DialoguesLoader>()
+    .add_asset::<JobBoard>()
+    .init_asset_loader::<JobBoardLoader>()
+    .add_asset::<Routes>()
+    .init_asset_loader::<RoutesLoader>()
+    .add_asset::<ItemCatalog>()
+    .init_asset_loader::<ItemCatalogLoader>()
     .add_plugin(RapierPhysicsPlugin::<NoUserData>::default())
     .add_state(GameState::AssetLoading)
     .add_startup_system(set_up_camera)
     .add_startup_system(set_up_physics)
+    .add_startup_system(rng::log_seed)
+    .add_system_to_stage(CoreStage::First, replay::count_frames)
+    .add_system_to_stage(
+        CoreStage::PreUpdate,
+        action::track_gamepads
+            .after(InputSystem)
+            .label(Label::TrackGamepads),
+    )
+    .add_system_to_stage(
+        CoreStage::PreUpdate,
+        scene::pause::toggle_pause.after(Label::MapActions),
+    )
+    .add_system_to_stage(
+        CoreStage::PreUpdate,
+        focus::read_pointer
+            .after(UiSystem::Focus)
+            .label(Label::ReadPointer),
+    )
+    .add_system_to_stage(
+        CoreStage::PreUpdate,
+        focus::navigate
+            .after(Label::MapActions)
+            .after(Label::ReadPointer),
+    )
+    .add_system(focus::highlight)
+    .add_system(settings::apply_settings)
+    .add_system(jobs::track_jobs)
+    .add_system_set(
+        SystemSet::on_exit(GameState::AssetLoading).with_system(dialogue::validate_references),
+    )
     .add_system_set(SystemSet::on_enter(GameState::MainMenu).with_system(scene::menu::setup))
     .add_system_set(
         SystemSet::on_update(GameState::MainMenu)
@@ -96,15 +252,40 @@ fn main() {
             .with_system(animation::animate),
     )
     .add_system_set(SystemSet::on_exit(GameState::MainMenu).with_system(scene::menu::teardown))
+    .add_system_set(
+        SystemSet::on_enter(GameState::HighScores).with_system(scene::high_scores::setup),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::HighScores)
+            .with_system(scene::high_scores::show_page)
+            .with_system(scene::high_scores::choose),
+    )
+    .add_system_set(
+        SystemSet::on_exit(GameState::HighScores).with_system(scene::high_scores::teardown),
+    )
     .add_system_set(
         SystemSet::on_enter(GameState::Outside)
             .with_system(scene::outside::setup)
+            .with_system(scene::outside::npc::spawn_npcs)
+            .with_system(jobs::setup_tracker)
             .before(Label::ReadInput),
     )
     .add_system_set(
         SystemSet::on_update(GameState::Outside)
             .with_system(control::read_control_input.label(Label::ReadInput))
+            .with_system(scene::outside::track_doors.before(Label::ReadInput))
+            .with_system(scene::outside::npc::track_nearby_npc.before(Label::ReadInput))
+            .with_system(scene::outside::update_interact_prompt.after(Label::ReadInput))
+            .with_system(scene::outside::update_meowney_display)
             .with_system(scene::outside::scene_transition.label(Label::ReadInput))
+            .with_system(dialogue::start_conversation.label(Label::ReadInput))
+            .with_system(dialogue::show_conversation.after(Label::ReadInput))
+            .with_system(dialogue::choose)
+            .with_system(jobs::update_tracker)
+            .with_system(scene::outside::ticket_booth::show_booth)
+            .with_system(scene::outside::ticket_booth::choose)
+            .with_system(inventory::toggle_panel.label(Label::ReadInput))
+            .with_system(inventory::update_panel.after(Label::ReadInput))
             .with_system(
                 control::update_facing
                     .after(Label::ReadInput)
@@ -131,7 +312,27 @@ fn main() {
                     .label(Label::Animate),
             ),
     )
-    .add_system_set(SystemSet::on_exit(GameState::Outside).with_system(scene::outside::teardown))
+    .add_system_set(
+        SystemSet::on_exit(GameState::Outside)
+            .with_system(scene::outside::teardown)
+            .with_system(dialogue::teardown)
+            .with_system(jobs::teardown_tracker)
+            .with_system(scene::outside::ticket_booth::teardown)
+            .with_system(inventory::teardown_panel),
+    )
+    .add_system_set(
+        SystemSet::on_enter(GameState::PostOfficePrompt)
+            .with_system(scene::post_office::prompt::setup),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::PostOfficePrompt)
+            .with_system(scene::post_office::prompt::choose)
+            .with_system(scene::post_office::prompt::highlight_selected),
+    )
+    .add_system_set(
+        SystemSet::on_exit(GameState::PostOfficePrompt)
+            .with_system(scene::post_office::prompt::teardown),
+    )
     .add_system_set(
         SystemSet::on_enter(GameState::PostOffice)
             .with_system(scene::post_office::setup)
@@ -158,19 +359,72 @@ fn main() {
                     .label(Label::Grow)
                     .after(Label::Eat),
             )
+            .with_system(
+                scene::post_office::expire_food
+                    .after(Label::Eat)
+                    .before(Label::SpawnFood),
+            )
             .with_system(
                 scene::post_office::food_spawner
                     .label(Label::SpawnFood)
                     .after(Label::Grow),
             )
-            .with_system(scene::post_office::game_over.after(Label::SpawnFood)),
+            .with_system(scene::post_office::game_over.after(Label::SpawnFood))
+            .with_system(scene::post_office::autopilot::steer.after(Label::SpawnFood))
+            .with_system(scene::post_office::autopilot::stop_attract_mode.after(Label::SpawnFood)),
+    )
+    .add_system_set(
+        SystemSet::on_exit(GameState::PostOffice).with_system(scene::post_office::teardown),
+    )
+    .add_system_set(
+        SystemSet::on_enter(GameState::PostOfficeResults)
+            .with_system(scene::post_office::results::setup),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::PostOfficeResults)
+            .with_system(scene::post_office::results::choose),
+    )
+    .add_system_set(
+        SystemSet::on_exit(GameState::PostOfficeResults)
+            .with_system(scene::post_office::results::teardown),
+    )
+    .add_system_set(
+        SystemSet::on_enter(GameState::Paused)
+            .with_system(focus::cover)
+            .with_system(scene::pause::setup)
+            .with_system(scene::pause::freeze_physics),
+    )
+    .add_system_set(SystemSet::on_update(GameState::Paused).with_system(scene::pause::click_item))
+    .add_system_set(
+        SystemSet::on_exit(GameState::Paused)
+            .with_system(scene::pause::teardown)
+            .with_system(scene::pause::unfreeze_physics)
+            .with_system(focus::uncover),
+    )
+    .add_system_set(
+        SystemSet::on_enter(GameState::Settings)
+            .with_system(focus::cover)
+            .with_system(scene::settings::setup),
+    )
+    .add_system_set(
+        SystemSet::on_update(GameState::Settings)
+            .with_system(scene::settings::click_item)
+            .with_system(scene::settings::update_labels),
+    )
+    .add_system_set(
+        SystemSet::on_exit(GameState::Settings)
+            .with_system(scene::settings::teardown)
+            .with_system(focus::uncover),
     )
     .add_system_set(SystemSet::on_enter(GameState::End).with_system(scene::end::setup))
     .add_system_set(SystemSet::on_update(GameState::End).with_system(scene::end::switch_end_scene))
     .add_system_set_to_stage(
         CoreStage::PostUpdate,
-        SystemSet::new().with_system(scene::post_office::position_translation),
+        SystemSet::new()
+            .with_system(scene::post_office::position_translation)
+            .with_system(scene::post_office::update_snake_sprites),
     )
+    .add_system_to_stage(CoreStage::Last, save::autosave)
     .register_type::<Animation>();
 
     if args.inspector {
@@ -181,7 +435,48 @@ fn main() {
         app.add_plugin(RapierRenderPlugin);
     }
 
+    // The attract mode demo's game would end up in a recording or a replay
+    if args.record.is_none() && replayer.is_none() {
+        app.add_system_set(
+            SystemSet::on_update(GameState::MainMenu)
+                .with_system(scene::post_office::autopilot::start_attract_mode),
+        );
+    }
+
+    if let Some(path) = args.record {
+        app.insert_resource(Recorder::create(&path, seed)?)
+            .add_system_to_stage(CoreStage::PostUpdate, replay::record_input)
+            .add_system_to_stage(CoreStage::PostUpdate, replay::record_snake)
+            .add_system_set(
+                SystemSet::on_update(GameState::PostOffice)
+                    .with_system(replay::record_shift.before(Label::ApplyInput)),
+            );
+    }
+
+    if let Some(replayer) = replayer {
+        app.insert_resource(replayer)
+            .add_system_to_stage(
+                CoreStage::PreUpdate,
+                replay::replay_input
+                    .after(Label::TrackGamepads)
+                    .label(Label::MapActions),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::PostOffice)
+                    .with_system(replay::replay_snake.label(Label::ApplyInput)),
+            );
+    } else {
+        app.add_system_to_stage(
+            CoreStage::PreUpdate,
+            action::map_actions
+                .after(Label::TrackGamepads)
+                .label(Label::MapActions),
+        );
+    }
+
     app.run();
+
+    Ok(())
 }
 
 #[derive(Component)]
@@ -198,6 +493,10 @@ fn set_up_camera(mut commands: Commands) {
         .spawn_bundle(camera_bundle)
         .insert(Name::new("Camera"))
         .insert(Camera);
+
+    commands
+        .spawn_bundle(UiCameraBundle::default())
+        .insert(Name::new("UI Camera"));
 }
 
 #[instrument(skip(rapier_config))]
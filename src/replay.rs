@@ -0,0 +1,501 @@
+//! Recording input to a file and replaying it in place of the keyboard, gamepads and mouse.
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context};
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    action::{Action, LeftStick},
+    focus::{Pointed, Pointer},
+    scene::post_office::{
+        snake::{Arena, Direction, GameEvent, GameOver, Position, SnakeGame},
+        Food, GridPosition, Snake, SnakeGameOver, SpawnFood,
+    },
+};
+
+/// Version of the replay file format.
+///
+/// 2. Actions and button clicks are recorded instead of movement.
+/// 3. Games of snake are recorded by step.
+pub const REPLAY_VERSION: u32 = 3;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ReplayHeader {
+    pub version: u32,
+    #[serde(default)]
+    pub seed: Option<u64>,
+}
+
+/// How a game of snake was set up, enough to start it again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ShiftSetup {
+    pub seed: u64,
+    pub arena: Arena,
+    pub start: Position,
+    pub heading: Direction,
+    pub length: usize,
+    pub obstacles: Vec<Position>,
+}
+
+impl ShiftSetup {
+    fn of(game: &SnakeGame) -> Self {
+        let mut obstacles = game.obstacles().collect::<Vec<_>>();
+        obstacles.sort_unstable_by_key(|cell| (cell.x, cell.y));
+
+        Self {
+            seed: game.seed(),
+            arena: *game.arena(),
+            start: game.head(),
+            heading: game.heading(),
+            length: game.length(),
+            obstacles,
+        }
+    }
+
+    pub fn game(&self) -> SnakeGame {
+        SnakeGame::new(self.arena, self.start, self.heading, self.length, self.seed)
+            .with_obstacles(self.obstacles.iter().copied())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub enum ReplayEvent {
+    Actions {
+        frame: u64,
+        pressed: Vec<Action>,
+        stick: (f32, f32),
+    },
+    Pointer {
+        frame: u64,
+        pointed: Option<Pointed>,
+    },
+    Shift {
+        frame: u64,
+        setup: ShiftSetup,
+    },
+    Snake {
+        step: u64,
+        event: GameEvent,
+    },
+    ShiftEnded {
+        frame: u64,
+        step: u64,
+        result: GameOver,
+    },
+}
+
+/// Reads a replay's header and events.
+pub fn read(reader: impl BufRead) -> anyhow::Result<(ReplayHeader, VecDeque<ReplayEvent>)> {
+    let mut lines = reader.lines();
+
+    let header: ReplayHeader = match lines.next() {
+        Some(line) => ron::de::from_str(&line?).context("failed to parse replay header")?,
+        None => bail!("replay is empty"),
+    };
+    if header.version != REPLAY_VERSION {
+        bail!(
+            "replay file version {} is not supported, expected {REPLAY_VERSION}",
+            header.version
+        );
+    }
+
+    let events = lines
+        .enumerate()
+        .map(|(index, line)| {
+            ron::de::from_str(&line?)
+                .with_context(|| format!("failed to parse replay event on line {}", index + 2))
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok((header, events))
+}
+
+/// Number of frames since startup, counted in [`CoreStage::First`].
+#[derive(Default, Debug)]
+pub struct FrameCount(pub u64);
+
+pub fn count_frames(mut frame_count: ResMut<FrameCount>) {
+    frame_count.0 += 1;
+}
+
+/// Writes replay events to the file given with `--record`.
+pub struct Recorder {
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, seed: u64) -> anyhow::Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("failed to create replay file {}", path.display()))?;
+        let mut recorder = Self {
+            writer: BufWriter::new(file),
+        };
+        recorder.write_line(&ReplayHeader {
+            version: REPLAY_VERSION,
+            seed: Some(seed),
+        })?;
+
+        Ok(recorder)
+    }
+
+    fn write_line<T: Serialize>(&mut self, value: &T) -> anyhow::Result<()> {
+        writeln!(self.writer, "{}", ron::ser::to_string(value)?)?;
+        // Flush every line so a recording survives the game crashing
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn record(&mut self, event: ReplayEvent) {
+        if let Err(error) = self.write_line(&event) {
+            warn!(?error, "failed to record replay event");
+        }
+    }
+}
+
+/// Records changes to the actions held, the stick and the mouse pointer.
+#[instrument(skip(recorder, frame_count, actions, stick, pointer, last_actions))]
+pub fn record_input(
+    mut recorder: ResMut<Recorder>,
+    frame_count: Res<FrameCount>,
+    actions: Res<Input<Action>>,
+    stick: Res<LeftStick>,
+    pointer: Res<Pointer>,
+    mut last_actions: Local<Option<(Vec<Action>, Vec2)>>,
+) {
+    let frame = frame_count.0;
+
+    let pressed = Action::ALL
+        .into_iter()
+        .filter(|action| actions.pressed(*action))
+        .collect::<Vec<_>>();
+    let current = (pressed, stick.0);
+    if last_actions.as_ref() != Some(&current) {
+        recorder.record(ReplayEvent::Actions {
+            frame,
+            pressed: current.0.clone(),
+            stick: (current.1.x, current.1.y),
+        });
+        *last_actions = Some(current);
+    }
+
+    if pointer.is_changed() {
+        recorder.record(ReplayEvent::Pointer {
+            frame,
+            pointed: pointer.0,
+        });
+    }
+}
+
+/// Records how a game of snake was set up.
+pub fn record_shift(
+    mut recorder: ResMut<Recorder>,
+    frame_count: Res<FrameCount>,
+    snake: Res<Snake>,
+) {
+    if snake.is_added() {
+        recorder.record(ReplayEvent::Shift {
+            frame: frame_count.0,
+            setup: ShiftSetup::of(&snake.0),
+        });
+    }
+}
+
+/// Records turns and expired food in the game of snake, and how it ended.
+#[instrument(skip(recorder, frame_count, snake, game_over_reader, recorded))]
+pub fn record_snake(
+    mut recorder: ResMut<Recorder>,
+    frame_count: Res<FrameCount>,
+    snake: Option<Res<Snake>>,
+    mut game_over_reader: EventReader<SnakeGameOver>,
+    mut recorded: Local<usize>,
+) {
+    let snake = match snake {
+        Some(snake) => snake,
+        None => return,
+    };
+    if snake.is_added() {
+        *recorded = 0;
+    }
+
+    for (step, event) in &snake.0.history()[*recorded..] {
+        recorder.record(ReplayEvent::Snake {
+            step: *step,
+            event: *event,
+        });
+    }
+    *recorded = snake.0.history().len();
+
+    for SnakeGameOver(result) in game_over_reader.iter() {
+        recorder.record(ReplayEvent::ShiftEnded {
+            frame: frame_count.0,
+            step: snake.0.steps(),
+            result: *result,
+        });
+    }
+}
+
+struct ReplayedShift {
+    history: VecDeque<(u64, GameEvent)>,
+    ending: Option<(u64, GameOver)>,
+    over: bool,
+}
+
+/// Plays back the file given with `--replay` in place of the keyboard, gamepads and mouse.
+pub struct Replayer {
+    seed: Option<u64>,
+    events: VecDeque<ReplayEvent>,
+    /// Frames to take off the frame count to get the matching frame of the recording.
+    offset: i64,
+    pressed: Vec<Action>,
+    stick: Vec2,
+    shift: Option<ReplayedShift>,
+    finished: bool,
+}
+
+impl Replayer {
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("failed to open replay file {}", path.display()))?;
+        let (header, events) = read(BufReader::new(file))
+            .with_context(|| format!("failed to read replay file {}", path.display()))?;
+
+        Ok(Self {
+            seed: header.seed,
+            events,
+            offset: 0,
+            pressed: Vec::new(),
+            stick: Vec2::ZERO,
+            shift: None,
+            finished: false,
+        })
+    }
+
+    pub fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    fn start_shift(&mut self, frame: u64, game: &SnakeGame) {
+        let index = self
+            .events
+            .iter()
+            .position(|event| matches!(event, ReplayEvent::Shift { .. }));
+        let (recorded_frame, setup) = match index.and_then(|index| self.events.remove(index)) {
+            Some(ReplayEvent::Shift { frame, setup }) => (frame, setup),
+            _ => {
+                warn!("game of snake started that isn't in the replay");
+                self.shift = None;
+                return;
+            }
+        };
+        if setup != ShiftSetup::of(game) {
+            warn!(recorded = ?setup, "game of snake was set up differently when recorded");
+        }
+        self.offset = frame as i64 - recorded_frame as i64;
+
+        // Turns and expired food are interleaved with input recorded during the game
+        let mut history = VecDeque::new();
+        let mut ending = None;
+        let mut index = index.unwrap_or_default();
+        while let Some(event) = self.events.get(index) {
+            match *event {
+                ReplayEvent::Snake { step, event } => {
+                    history.push_back((step, event));
+                    self.events.remove(index);
+                }
+                ReplayEvent::ShiftEnded { step, result, .. } => {
+                    ending = Some((step, result));
+                    break;
+                }
+                ReplayEvent::Shift { .. } => break,
+                _ => index += 1,
+            }
+        }
+
+        debug!(turns = history.len(), ?ending, "replaying game of snake");
+        self.shift = Some(ReplayedShift {
+            history,
+            ending,
+            over: false,
+        });
+    }
+}
+
+#[instrument(skip(
+    replayer,
+    frame_count,
+    actions,
+    stick,
+    pointer,
+    snake,
+    game_over_reader
+))]
+pub fn replay_input(
+    mut replayer: ResMut<Replayer>,
+    frame_count: Res<FrameCount>,
+    mut actions: ResMut<Input<Action>>,
+    mut stick: ResMut<LeftStick>,
+    mut pointer: ResMut<Pointer>,
+    snake: Option<Res<Snake>>,
+    mut game_over_reader: EventReader<SnakeGameOver>,
+) {
+    let replayer = &mut *replayer;
+
+    for SnakeGameOver(result) in game_over_reader.iter() {
+        if let (Some(shift), Some(snake)) = (&mut replayer.shift, &snake) {
+            let ending = (snake.0.steps(), *result);
+            if shift.ending != Some(ending) {
+                warn!(
+                    ?ending,
+                    recorded = ?shift.ending,
+                    "game of snake ended differently when recorded"
+                );
+            }
+            shift.over = true;
+        }
+    }
+
+    while let Some(event) = replayer.events.front() {
+        let frame = frame_count.0 as i64 - replayer.offset;
+        match *event {
+            ReplayEvent::Actions {
+                frame: at,
+                ref pressed,
+                stick: (x, y),
+            } if at as i64 <= frame => {
+                replayer.pressed = pressed.clone();
+                replayer.stick = Vec2::new(x, y);
+            }
+            ReplayEvent::Pointer { frame: at, pointed } if at as i64 <= frame => {
+                pointer.0 = pointed;
+            }
+            // The replayed game ended on the previous frame, so carry on from there
+            ReplayEvent::ShiftEnded { frame: at, .. }
+                if replayer.shift.as_ref().map_or(false, |shift| shift.over) =>
+            {
+                replayer.offset = frame_count.0 as i64 - 1 - at as i64;
+                replayer.shift = None;
+            }
+            // Later input, or a game of snake that has yet to start or end
+            _ => break,
+        }
+        replayer.events.pop_front();
+    }
+
+    actions.clear();
+    for action in Action::ALL {
+        if replayer.pressed.contains(&action) {
+            actions.press(action);
+        } else if actions.pressed(action) {
+            actions.release(action);
+        }
+    }
+    stick.0 = replayer.stick;
+
+    if replayer.events.is_empty() && !replayer.finished {
+        info!(frame = frame_count.0, "replay finished");
+        replayer.finished = true;
+    }
+}
+
+/// Applies recorded turns and expired food to the game of snake.
+#[instrument(skip(commands, replayer, frame_count, snake, food, spawn_writer))]
+pub fn replay_snake(
+    mut commands: Commands,
+    mut replayer: ResMut<Replayer>,
+    frame_count: Res<FrameCount>,
+    mut snake: ResMut<Snake>,
+    food: Query<(Entity, &GridPosition), With<Food>>,
+    mut spawn_writer: EventWriter<SpawnFood>,
+) {
+    if snake.is_added() {
+        replayer.start_shift(frame_count.0, &snake.0);
+    }
+    let shift = match &mut replayer.shift {
+        Some(shift) => shift,
+        None => return,
+    };
+
+    while let Some((step, event)) = shift.history.front().copied() {
+        if step > snake.0.steps() {
+            break;
+        }
+        shift.history.pop_front();
+
+        if let GameEvent::Expire(position) = event {
+            for (entity, grid_position) in food.iter() {
+                if grid_position.0 == position {
+                    commands.entity(entity).despawn();
+                }
+            }
+        }
+        if let Some(replacement) = snake.0.apply(event) {
+            spawn_writer.send(SpawnFood(replacement));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How each game of snake in a replay ended, and how it was recorded to have ended.
+    fn play_shifts(replay: &str) -> Vec<((u64, GameOver), (u64, GameOver))> {
+        let (_, events) = read(replay.as_bytes()).unwrap();
+
+        let mut endings = Vec::new();
+        let mut game: Option<SnakeGame> = None;
+        let mut history = VecDeque::new();
+        for event in events {
+            match event {
+                ReplayEvent::Shift { setup, .. } => {
+                    let mut new_game = setup.game();
+                    new_game.spawn_food().unwrap();
+                    game = Some(new_game);
+                    history.clear();
+                }
+                ReplayEvent::Snake { step, event } => history.push_back((step, event)),
+                ReplayEvent::ShiftEnded { step, result, .. } => {
+                    let game = game.as_mut().unwrap();
+                    let ending = loop {
+                        while history.front().map_or(false, |(at, _)| *at == game.steps()) {
+                            let (_, event) = history.pop_front().unwrap();
+                            game.apply(event);
+                        }
+                        if let Err(game_over) = game.step() {
+                            break game_over;
+                        }
+                    };
+                    assert!(history.is_empty(), "turns left after the game ended");
+                    endings.push(((game.steps(), ending), (step, result)));
+                }
+                _ => (),
+            }
+        }
+
+        endings
+    }
+
+    #[test]
+    fn recorded_shifts_play_out_the_same_way() {
+        let endings = play_shifts(include_str!("../tests/replays/two_shifts.replay"));
+
+        for (played, recorded) in &endings {
+            assert_eq!(played, recorded);
+        }
+        assert_eq!(
+            endings
+                .iter()
+                .map(|((_, result), _)| *result)
+                .collect::<Vec<_>>(),
+            [GameOver::HitWall, GameOver::HitOwnTail]
+        );
+    }
+}
@@ -0,0 +1,264 @@
+//! Save games and story progress.
+
+use std::{collections::BTreeSet, marker::PhantomData, path::PathBuf};
+
+use anyhow::{bail, Context};
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::{
+    config, inventory::Inventory, jobs::Jobs, scene::post_office::autopilot::Autopilot, GameState,
+    Meowney,
+};
+
+/// Version of the save file format. Bump this whenever [`SaveGame`] changes shape, and give new
+/// fields a `#[serde(default)]` so older saves still load.
+pub const SAVE_VERSION: u32 = 3;
+
+const SAVE_FILE_NAME: &str = "save.ron";
+
+/// Story progress that isn't tracked by any other resource.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Progress {
+    pub flags: BTreeSet<String>,
+}
+
+/// Everything written to the save file.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SaveGame {
+    pub version: u32,
+    pub state: GameState,
+    pub meowney: u32,
+    #[serde(default)]
+    pub progress: Progress,
+    /// Added in version 2.
+    #[serde(default)]
+    pub jobs: Jobs,
+    /// Added in version 3.
+    #[serde(default)]
+    pub inventory: Inventory,
+}
+
+impl SaveGame {
+    pub fn capture(
+        state: GameState,
+        meowney: &Meowney,
+        progress: &Progress,
+        jobs: &Jobs,
+        inventory: &Inventory,
+    ) -> Self {
+        Self {
+            version: SAVE_VERSION,
+            state,
+            meowney: meowney.0,
+            progress: progress.clone(),
+            jobs: jobs.clone(),
+            inventory: inventory.clone(),
+        }
+    }
+
+    /// Checks a save read from disk can be loaded, and brings saves from older versions up to
+    /// date.
+    fn migrate(mut self) -> anyhow::Result<Self> {
+        if self.version > SAVE_VERSION {
+            bail!(
+                "save file version {} is newer than supported version {SAVE_VERSION}",
+                self.version
+            );
+        }
+        // Version 1 saves could be made mid-shift, which can't be resumed
+        if self.state == GameState::PostOffice {
+            self.state = GameState::Outside;
+        }
+        if !self.state.is_saveable() {
+            bail!("save file has unsaveable state {:?}", self.state);
+        }
+
+        self.version = SAVE_VERSION;
+        Ok(self)
+    }
+}
+
+/// Location of the save file, if the platform has a data directory.
+#[derive(Debug)]
+pub struct SaveFile(pub Option<PathBuf>);
+
+impl SaveFile {
+    pub fn locate() -> Self {
+        Self(config::data_dir().map(|dir| dir.join(SAVE_FILE_NAME)))
+    }
+
+    pub fn exists(&self) -> bool {
+        self.0.as_ref().map_or(false, |path| path.exists())
+    }
+
+    pub fn read(&self) -> anyhow::Result<SaveGame> {
+        let path = self.0.as_ref().context("no data directory")?;
+        let save: SaveGame = config::read(path)?;
+        save.migrate()
+    }
+
+    pub fn write(&self, save: &SaveGame) -> anyhow::Result<()> {
+        let path = self.0.as_ref().context("no data directory")?;
+        config::write(path, save)
+    }
+}
+
+/// What a save file captures besides the state: everything the player has.
+#[derive(SystemParam)]
+pub struct SaveData<'w, 's> {
+    pub meowney: Res<'w, Meowney>,
+    pub progress: Res<'w, Progress>,
+    pub jobs: Res<'w, Jobs>,
+    pub inventory: Res<'w, Inventory>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl SaveData<'_, '_> {
+    pub fn is_changed(&self) -> bool {
+        self.meowney.is_changed()
+            || self.progress.is_changed()
+            || self.jobs.is_changed()
+            || self.inventory.is_changed()
+    }
+
+    fn capture(&self, state: GameState) -> SaveGame {
+        SaveGame::capture(
+            state,
+            &self.meowney,
+            &self.progress,
+            &self.jobs,
+            &self.inventory,
+        )
+    }
+}
+
+/// Writes the save file whenever the game moves into a new saveable state, when what the player
+/// has changes in town, and when the player leaves for the main menu or quits.
+#[instrument(skip(state, last_state, last_playing, exits, save_file, data, autopilot))]
+pub fn autosave(
+    state: Res<State<GameState>>,
+    mut last_state: Local<Option<GameState>>,
+    mut last_playing: Local<Option<GameState>>,
+    mut exits: EventReader<AppExit>,
+    save_file: Res<SaveFile>,
+    data: SaveData,
+    autopilot: Option<Res<Autopilot>>,
+) {
+    let current = state.current();
+    // The saveable state the player is in, even with the pause menu open over it
+    let playing = state
+        .inactives()
+        .iter()
+        .chain([current])
+        .rev()
+        .find(|state| state.is_saveable())
+        .cloned();
+    let entered = last_state.as_ref() != Some(current);
+    let left = exits.iter().count() > 0 || (entered && *current == GameState::MainMenu);
+    *last_state = Some(current.clone());
+    let was_playing = std::mem::replace(&mut *last_playing, playing.clone());
+
+    // The attract mode demo isn't the player's game
+    if autopilot.is_some() {
+        return;
+    }
+
+    let save_state = if left {
+        playing.or(was_playing)
+    } else if (entered && current.is_saveable())
+        || (*current == GameState::Outside && data.is_changed())
+    {
+        Some(current.clone())
+    } else {
+        None
+    };
+    let save = match save_state {
+        Some(state) => data.capture(state),
+        None => return,
+    };
+    match save_file.write(&save) {
+        Ok(()) => debug!(state = ?save.state, meowney = save.meowney, "autosaved"),
+        Err(error) => warn!(?error, "failed to autosave"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jobs::JobState;
+
+    fn sample() -> SaveGame {
+        let mut progress = Progress::default();
+        progress.flags.insert("met_postmaster".to_string());
+        let mut jobs = Jobs::default();
+        jobs.states.insert(
+            "parcel_rush".to_string(),
+            JobState::Active {
+                progress: vec![3, 1],
+            },
+        );
+        let mut inventory = Inventory::default();
+        inventory.items.insert("lucky_stamp".to_string(), 2);
+
+        SaveGame::capture(
+            GameState::Outside,
+            &Meowney(42),
+            &progress,
+            &jobs,
+            &inventory,
+        )
+    }
+
+    #[test]
+    fn round_trips_through_the_save_file() {
+        let dir = std::env::temp_dir().join(format!("meowdy-save-test-{}", std::process::id()));
+        let save_file = SaveFile(Some(dir.join(SAVE_FILE_NAME)));
+        let save = sample();
+
+        save_file.write(&save).unwrap();
+        let loaded = save_file.read().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(loaded.version, SAVE_VERSION);
+        assert_eq!(loaded.state, save.state);
+        assert_eq!(loaded.meowney, save.meowney);
+        assert_eq!(loaded.progress.flags, save.progress.flags);
+        assert_eq!(loaded.jobs.states, save.jobs.states);
+        assert_eq!(loaded.inventory.items, save.inventory.items);
+    }
+
+    #[test]
+    fn rejects_saves_from_newer_versions() {
+        let save = SaveGame {
+            version: SAVE_VERSION + 1,
+            ..sample()
+        };
+        assert!(save.migrate().is_err());
+    }
+
+    #[test]
+    fn loads_version_1_saves() {
+        let save: SaveGame =
+            ron::de::from_str("(version: 1, state: PostOffice, meowney: 7, progress: (flags: []))")
+                .unwrap();
+        let save = save.migrate().unwrap();
+
+        assert_eq!(save.version, SAVE_VERSION);
+        assert_eq!(save.state, GameState::Outside);
+        assert_eq!(save.meowney, 7);
+        assert!(save.jobs.states.is_empty());
+        assert!(save.inventory.items.is_empty());
+    }
+
+    #[test]
+    fn rejects_unsaveable_states() {
+        let save = SaveGame {
+            state: GameState::MainMenu,
+            ..sample()
+        };
+        assert!(save.migrate().is_err());
+    }
+}
@@ -1,4 +1,7 @@
 pub mod end;
+pub mod high_scores;
 pub mod menu;
 pub mod outside;
+pub mod pause;
 pub mod post_office;
+pub mod settings;
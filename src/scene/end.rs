@@ -1,11 +1,57 @@
 use bevy::prelude::*;
 
-use crate::{assets::Images, Camera};
+use crate::{
+    assets::{Fonts, Images},
+    save::Progress,
+    scene::outside::{npc::TownData, ticket_booth::Routes},
+    Camera,
+};
 
 #[derive(Component)]
 pub struct EndSceneOne(pub Timer);
 
-pub fn setup(mut commands: Commands, images: Res<Images>, camera: Query<Entity, With<Camera>>) {
+pub fn setup(
+    mut commands: Commands,
+    images: Res<Images>,
+    fonts: Res<Fonts>,
+    progress: Res<Progress>,
+    town_data: Res<TownData>,
+    routes: Res<Assets<Routes>>,
+    camera: Query<Entity, With<Camera>>,
+) {
+    // Name the route the player left town on
+    let destination = routes.get(&town_data.routes).and_then(|routes| {
+        routes
+            .routes
+            .iter()
+            .find(|route| progress.flags.contains(&route.departed_flag()))
+    });
+    if let Some(route) = destination {
+        commands
+            .spawn_bundle(TextBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    position: Rect {
+                        bottom: Val::Px(32.),
+                        left: Val::Px(32.),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    format!("Next stop: {}", route.destination),
+                    TextStyle {
+                        font: fonts.vt323.clone(),
+                        font_size: 48.,
+                        color: Color::WHITE,
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            })
+            .insert(Name::new("Destination"));
+    }
+
     commands
         .spawn()
         .insert_bundle(SpriteBundle {
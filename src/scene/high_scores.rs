@@ -0,0 +1,233 @@
+//! High score page, opened from the main menu, and the table shared with the snake results.
+
+use bevy::prelude::*;
+use tracing::instrument;
+
+use crate::{
+    assets::Fonts,
+    focus::{Activated, Focusable},
+    high_scores::{HighScore, HighScores, MAX_HIGH_SCORES},
+    ui::{BUTTON_COLOR, HIGHLIGHT_COLOR, PANEL_COLOR},
+    GameState,
+};
+
+#[derive(Component)]
+pub struct HighScorePage;
+
+/// Index into [`HighScores::arenas`] of the kind of arena whose table is shown.
+#[derive(Debug, Default)]
+pub struct ShownArena(pub usize);
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HighScoreButton {
+    PreviousArena,
+    NextArena,
+    BackToMenu,
+}
+
+/// Adds a row of text for each of the top `count` runs in `entries`, highlighting the one at
+/// `highlight`.
+pub fn spawn_table<'a>(
+    parent: &mut ChildBuilder,
+    entries: impl Iterator<Item = &'a HighScore>,
+    count: usize,
+    highlight: Option<usize>,
+    style: TextStyle,
+) {
+    let mut entries = entries.take(count).peekable();
+    if entries.peek().is_none() {
+        parent.spawn_bundle(TextBundle {
+            text: Text::with_section("No deliveries yet", style, Default::default()),
+            ..Default::default()
+        });
+        return;
+    }
+
+    for (rank, entry) in entries.enumerate() {
+        let line = format!(
+            "{:>2}. {:>4} M  length {:>3}  {:<6}  {}  seed {}",
+            rank + 1,
+            entry.score,
+            entry.length,
+            entry.difficulty.name(),
+            entry.date,
+            entry.seed
+        );
+        let color = if highlight == Some(rank) {
+            HIGHLIGHT_COLOR
+        } else {
+            style.color
+        };
+
+        parent.spawn_bundle(TextBundle {
+            style: Style {
+                margin: Rect::all(Val::Px(2.)),
+                ..Default::default()
+            },
+            text: Text::with_section(
+                line,
+                TextStyle {
+                    color,
+                    ..style.clone()
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        });
+    }
+}
+
+/// Starts the page on the table with the best run, which [`show_page`] then draws.
+#[instrument(skip(shown))]
+pub fn setup(mut shown: ResMut<ShownArena>) {
+    info!("setting up high score page");
+    shown.0 = 0;
+}
+
+/// Draws the page with the table for the arena on show, whenever it changes.
+pub fn show_page(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    high_scores: Res<HighScores>,
+    shown: Res<ShownArena>,
+    pages: Query<Entity, With<HighScorePage>>,
+) {
+    if !shown.is_changed() {
+        return;
+    }
+    pages.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let text_style = |font_size| TextStyle {
+        font: fonts.vt323.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+    let arenas = high_scores.arenas();
+    let arena = arenas.get(shown.0).copied();
+    let buttons = if arenas.len() > 1 {
+        vec![
+            (HighScoreButton::PreviousArena, "Previous"),
+            (HighScoreButton::NextArena, "Next"),
+            (HighScoreButton::BackToMenu, "Back"),
+        ]
+    } else {
+        vec![(HighScoreButton::BackToMenu, "Back")]
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: PANEL_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(Name::new("High Score Page"))
+        .insert(HighScorePage)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(24.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "Best post office shifts",
+                    text_style(64.),
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            if let Some(arena) = arena {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(8.)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(
+                        format!("{} ({}/{})", arena.name(), shown.0 + 1, arenas.len()),
+                        text_style(44.),
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+            let entries = arena
+                .into_iter()
+                .flat_map(|arena| high_scores.for_arena(arena));
+            spawn_table(parent, entries, MAX_HIGH_SCORES, None, text_style(36.));
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        margin: Rect::all(Val::Px(16.)),
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for (index, (button, label)) in buttons.into_iter().enumerate() {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    margin: Rect::all(Val::Px(8.)),
+                                    padding: Rect::all(Val::Px(8.)),
+                                    ..Default::default()
+                                },
+                                color: BUTTON_COLOR.into(),
+                                ..Default::default()
+                            })
+                            .insert(button)
+                            .insert(Focusable(index))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        label,
+                                        text_style(36.),
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+        });
+}
+
+/// Flips between the tables for each kind of arena, or goes back to the main menu.
+#[instrument(skip(activated, query, high_scores, shown, app_state))]
+pub fn choose(
+    mut activated: EventReader<Activated>,
+    query: Query<&HighScoreButton>,
+    high_scores: Res<HighScores>,
+    mut shown: ResMut<ShownArena>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    let arenas = high_scores.arenas().len().max(1);
+    for button in activated
+        .iter()
+        .filter_map(|Activated(entity)| query.get(*entity).ok())
+    {
+        match button {
+            HighScoreButton::PreviousArena => shown.0 = (shown.0 + arenas - 1) % arenas,
+            HighScoreButton::NextArena => shown.0 = (shown.0 + 1) % arenas,
+            HighScoreButton::BackToMenu => app_state.set(GameState::MainMenu).unwrap(),
+        }
+        return;
+    }
+}
+
+#[instrument(skip(commands, query))]
+pub fn teardown(mut commands: Commands, query: Query<Entity, With<HighScorePage>>) {
+    info!("tearing down high score page");
+    query.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
@@ -1,16 +1,33 @@
 use bevy::prelude::*;
 use tracing::instrument;
 
-use crate::{animation::Animation, assets::Sprites, GameState};
+use crate::{
+    animation::Animation,
+    assets::{Fonts, Sprites},
+    focus::{Activated, Focusable},
+    inventory::Inventory,
+    jobs::Jobs,
+    save::{Progress, SaveFile},
+    GameState, Meowney,
+};
 
 #[derive(Component)]
 pub struct MainMenu;
 
-#[instrument(skip(commands, sprites, texture_atlases))]
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuItem {
+    NewGame,
+    Continue,
+    HighScores,
+}
+
+#[instrument(skip(commands, sprites, fonts, texture_atlases, save_file))]
 pub fn setup(
     mut commands: Commands,
     sprites: Res<Sprites>,
+    fonts: Res<Fonts>,
     mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    save_file: Res<SaveFile>,
 ) {
     info!("entered menu");
 
@@ -40,6 +57,12 @@ pub fn setup(
         .insert(MainMenu)
         .insert(Name::new("Main Menu Background"));
 
+    let mut items = vec![(MenuItem::NewGame, "New game")];
+    if save_file.exists() {
+        items.push((MenuItem::Continue, "Continue"));
+    }
+    items.push((MenuItem::HighScores, "High scores"));
+
     commands
         .spawn_bundle(NodeBundle {
             style: Style {
@@ -49,40 +72,90 @@ pub fn setup(
                 },
                 flex_direction: FlexDirection::ColumnReverse,
                 align_items: AlignItems::Center,
-                justify_content: JustifyContent::SpaceEvenly,
+                justify_content: JustifyContent::FlexEnd,
+                padding: Rect {
+                    bottom: Val::Percent(15.),
+                    ..Default::default()
+                },
                 ..Default::default()
             },
-            visibility: Visibility { is_visible: false },
+            color: Color::NONE.into(),
             ..Default::default()
         })
         .insert(MainMenu)
         .with_children(|parent| {
-            parent.spawn_bundle(ButtonBundle {
-                style: Style {
-                    size: Size {
-                        width: Val::Percent(100.),
-                        height: Val::Percent(100.),
-                    },
-                    flex_direction: FlexDirection::ColumnReverse,
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::SpaceEvenly,
-                    ..Default::default()
-                },
-                ..Default::default()
-            });
+            for (index, (item, label)) in items.into_iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            padding: Rect::all(Val::Px(8.)),
+                            ..Default::default()
+                        },
+                        color: Color::NONE.into(),
+                        ..Default::default()
+                    })
+                    .insert(item)
+                    .insert(Focusable(index))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                label,
+                                TextStyle {
+                                    font: fonts.vt323.clone(),
+                                    font_size: 48.0,
+                                    color: Color::BLACK,
+                                },
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
         });
 }
 
-pub fn click_item(query: Query<&Interaction>, mut app_state: ResMut<State<GameState>>) {
-    for interaction in query.iter() {
-        match interaction {
-            Interaction::Clicked => {
-                info!("main menu clicked");
+#[instrument(skip(commands, activated, query, app_state, save_file))]
+pub fn click_item(
+    mut commands: Commands,
+    mut activated: EventReader<Activated>,
+    query: Query<&MenuItem>,
+    mut app_state: ResMut<State<GameState>>,
+    save_file: Res<SaveFile>,
+) {
+    for item in activated
+        .iter()
+        .filter_map(|Activated(entity)| query.get(*entity).ok())
+    {
+        match item {
+            MenuItem::NewGame => {
+                info!("starting new game");
+                commands.insert_resource(Meowney::default());
+                commands.insert_resource(Progress::default());
+                commands.insert_resource(Jobs::default());
+                commands.insert_resource(Inventory::default());
                 app_state.set(GameState::Outside).unwrap();
             }
-            Interaction::Hovered => (),
-            Interaction::None => (),
+            MenuItem::Continue => match save_file.read() {
+                Ok(save) => {
+                    info!(state = ?save.state, meowney = save.meowney, "continuing saved game");
+                    commands.insert_resource(Meowney(save.meowney));
+                    commands.insert_resource(save.progress);
+                    commands.insert_resource(save.jobs);
+                    commands.insert_resource(save.inventory);
+                    app_state.set(save.state).unwrap();
+                }
+                Err(error) => {
+                    warn!(?error, "failed to load save game");
+                    continue;
+                }
+            },
+            MenuItem::HighScores => {
+                info!("showing high scores");
+                app_state.set(GameState::HighScores).unwrap();
+            }
         }
+
+        return;
     }
 }
 
@@ -2,13 +2,22 @@ use bevy::prelude::*;
 use bevy_rapier2d::prelude::*;
 use tracing::instrument;
 
+use self::{
+    npc::{NearbyNpc, Npc, Town},
+    ticket_booth::{depart, TicketBooth},
+};
 use crate::{
     animation::Animation,
     assets::{Fonts, Images, Sprites},
-    control::{Controlled, Facing, Moves},
+    control::{Controlled, Facing, Interact, Moves},
+    inventory::Inventory,
+    save::Progress,
     GameState, Meowney,
 };
 
+pub mod npc;
+pub mod ticket_booth;
+
 #[derive(Component, Reflect, Clone, Debug, Default)]
 #[reflect(Component)]
 pub struct Player;
@@ -25,6 +34,43 @@ pub struct MeowneyDisplay;
 #[derive(Component)]
 pub struct MeowneyError;
 
+/// Places in town the player can go through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Door {
+    PostOffice,
+    Stagecoach,
+}
+
+impl Door {
+    const ALL: [Door; 2] = [Door::PostOffice, Door::Stagecoach];
+
+    fn name(self) -> &'static str {
+        match self {
+            Door::PostOffice => "Post Office Door",
+            Door::Stagecoach => "Stagecoach Stop",
+        }
+    }
+
+    /// Centre and half size of the zone in front of the door, in pixels.
+    fn zone(self) -> (Vec2, Vec2) {
+        match self {
+            Door::PostOffice => (Vec2::new(-115., 20.), Vec2::new(16., 16.)),
+            Door::Stagecoach => (Vec2::new(-2., 20.), Vec2::new(20., 16.)),
+        }
+    }
+}
+
+/// Sensor in front of a door that lets the player through while they stand in it.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct DoorZone(pub Door);
+
+/// The door whose zone the player is standing in, if any.
+#[derive(Default, Debug)]
+pub struct NearbyDoor(pub Option<Door>);
+
+#[derive(Component)]
+pub struct InteractPrompt;
+
 #[instrument(skip(commands, sprites, images, fonts, texture_atlases, rapier_config))]
 pub fn setup(
     mut commands: Commands,
@@ -205,8 +251,59 @@ pub fn setup(
         .insert(Name::new("Right Collider"))
         .insert(Collider);
 
+    for door in Door::ALL {
+        let (centre, half_size) = door.zone();
+        commands
+            .spawn()
+            .insert_bundle(RigidBodyBundle {
+                body_type: RigidBodyType::Static.into(),
+                position: (centre / scale).into(),
+                ..Default::default()
+            })
+            .insert_bundle(ColliderBundle {
+                collider_type: ColliderType::Sensor.into(),
+                shape: ColliderShape::cuboid(half_size.x / scale, half_size.y / scale).into(),
+                flags: ActiveEvents::INTERSECTION_EVENTS.into(),
+                ..Default::default()
+            })
+            .insert(RigidBodyPositionSync::Discrete)
+            .insert(ColliderDebugRender::default())
+            .insert(Name::new(door.name()))
+            .insert(DoorZone(door))
+            .insert(Collider);
+    }
+    commands.insert_resource(NearbyDoor::default());
+
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(48.),
+                    left: Val::Percent(50.),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: fonts.vt323.clone(),
+                    font_size: 48.0,
+                    color: Color::WHITE,
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Center,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            visibility: Visibility { is_visible: false },
+            ..Default::default()
+        })
+        .insert(Name::new("Interact Prompt"))
+        .insert(InteractPrompt);
+
     let font = fonts.vt323.clone();
-    commands.spawn_bundle(UiCameraBundle::default());
 
     commands
         .spawn_bundle(NodeBundle {
@@ -251,36 +348,167 @@ pub fn setup(
         .insert(MeowneyDisplay);
 }
 
-#[instrument(skip(keyboard_input, app_state))]
+/// Keeps track of which door zone the player is standing in.
+#[instrument(skip(events, zones, nearby))]
+pub fn track_doors(
+    mut events: EventReader<IntersectionEvent>,
+    zones: Query<&DoorZone>,
+    mut nearby: ResMut<NearbyDoor>,
+) {
+    for event in events.iter() {
+        let zone = [event.collider1.entity(), event.collider2.entity()]
+            .into_iter()
+            .find_map(|entity| zones.get(entity).ok());
+        let DoorZone(door) = match zone {
+            Some(zone) => *zone,
+            None => continue,
+        };
+
+        if event.intersecting {
+            debug!(?door, "entered door zone");
+            nearby.0 = Some(door);
+        } else if nearby.0 == Some(door) {
+            debug!(?door, "left door zone");
+            nearby.0 = None;
+        }
+    }
+}
+
+/// Shows what the interact key does while the player is at a door or next to someone.
+pub fn update_interact_prompt(
+    nearby_door: Res<NearbyDoor>,
+    nearby_npc: Res<NearbyNpc>,
+    npcs: Query<&Npc>,
+    interact: Interact,
+    town: Town,
+    inventory: Res<Inventory>,
+    mut prompts: Query<(&mut Text, &mut Visibility), With<InteractPrompt>>,
+) {
+    let key = interact.key_name();
+    let tickets = town.routes().map_or_else(Vec::new, |routes| {
+        routes
+            .tickets_held(&inventory)
+            .into_iter()
+            .map(|(_, route)| route)
+            .collect()
+    });
+    let door_message = nearby_door.0.map(|door| match (door, tickets.as_slice()) {
+        (Door::PostOffice, _) => format!("Press {key} to enter"),
+        (Door::Stagecoach, []) => "You need a ticket from the ticket agent".to_string(),
+        (Door::Stagecoach, [route]) => format!("Press {key} to ride to {}", route.destination),
+        (Door::Stagecoach, _) => format!("Press {key} to board the stagecoach"),
+    });
+    let npc_message = || {
+        nearby_npc
+            .0
+            .and_then(|entity| npcs.get(entity).ok())
+            .filter(|npc| npc.dialogue.is_some())
+            .map(|npc| format!("Press {key} to talk to {}", npc.name))
+    };
+    let message = if interact.is_suspended() {
+        None
+    } else {
+        door_message.or_else(npc_message)
+    };
+
+    // Only touch the prompt when it changes, so the text isn't laid out again every frame
+    for (mut text, mut visibility) in prompts.iter_mut() {
+        if visibility.is_visible != message.is_some() {
+            visibility.is_visible = message.is_some();
+        }
+        if let Some(message) = &message {
+            if text.sections[0].value != *message {
+                text.sections[0].value = message.clone();
+            }
+        }
+    }
+}
+
+/// Keeps the Meowney counter up to date when it changes in town.
+pub fn update_meowney_display(
+    meowney: Res<Meowney>,
+    displays: Query<&Children, With<MeowneyDisplay>>,
+    mut texts: Query<&mut Text>,
+) {
+    if !meowney.is_changed() {
+        return;
+    }
+
+    for children in displays.iter() {
+        for child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                text.sections[0].value = format!("Meowney: {}", meowney.0);
+            }
+        }
+    }
+}
+
+#[instrument(skip(commands, interact, nearby, town, inventory, progress, app_state))]
 pub fn scene_transition(
-    keyboard_input: Res<Input<KeyCode>>,
+    mut commands: Commands,
+    interact: Interact,
+    nearby: Res<NearbyDoor>,
+    town: Town,
+    mut inventory: ResMut<Inventory>,
+    mut progress: ResMut<Progress>,
     mut app_state: ResMut<State<GameState>>,
-    meowney: Res<Meowney>,
 ) {
-    if keyboard_input.pressed(KeyCode::Space) {
-        info!("transitioning to post office scene");
-        app_state.set(GameState::PostOffice).unwrap();
-    } else if keyboard_input.pressed(KeyCode::E) {
-        if meowney.0 >= 20 {
-            info!("transitioning to end scene");
-            app_state.set(GameState::End).unwrap();
-        } else {
-            warn!("player does not have enough meowney");
+    if !interact.just_pressed() {
+        return;
+    }
+
+    match nearby.0 {
+        Some(Door::PostOffice) => {
+            info!("transitioning to post office scene");
+            app_state.set(GameState::PostOfficePrompt).unwrap();
+        }
+        Some(Door::Stagecoach) => {
+            let routes = match town.routes() {
+                Some(routes) => routes,
+                None => return,
+            };
+            let tickets = routes
+                .tickets_held(&inventory)
+                .into_iter()
+                .map(|(_, route)| route)
+                .collect::<Vec<_>>();
+
+            match tickets.as_slice() {
+                [] => warn!("player does not have a stagecoach ticket"),
+                [route] => depart(route, &mut inventory, &mut progress, &mut app_state),
+                _ => {
+                    info!(tickets = tickets.len(), "choosing a stagecoach route");
+                    TicketBooth::Boarding.open(&mut commands);
+                }
+            }
         }
+        None => (),
     }
 }
 
-#[instrument(skip(commands, player, scenery, colliders, meowney_display))]
+#[instrument(skip(
+    commands,
+    player,
+    npcs,
+    scenery,
+    colliders,
+    meowney_display,
+    interact_prompt
+))]
 pub fn teardown(
     mut commands: Commands,
     player: Query<Entity, With<Player>>,
+    npcs: Query<Entity, With<Npc>>,
     scenery: Query<Entity, With<Scenery>>,
     colliders: Query<Entity, With<Collider>>,
     meowney_display: Query<Entity, With<MeowneyDisplay>>,
+    interact_prompt: Query<Entity, With<InteractPrompt>>,
 ) {
     info!("tearing down outside scene");
     player.for_each(|entity| commands.entity(entity).despawn_recursive());
+    npcs.for_each(|entity| commands.entity(entity).despawn_recursive());
     scenery.for_each(|entity| commands.entity(entity).despawn_recursive());
     colliders.for_each(|entity| commands.entity(entity).despawn_recursive());
     meowney_display.for_each(|entity| commands.entity(entity).despawn_recursive());
+    interact_prompt.for_each(|entity| commands.entity(entity).despawn_recursive());
 }
@@ -0,0 +1,274 @@
+//! Townsfolk standing around outside, placed by `assets/data/townsfolk.npcs`.
+
+use std::{marker::PhantomData, path::PathBuf};
+
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    ecs::system::SystemParam,
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use bevy_asset_loader::AssetCollection;
+use bevy_rapier2d::prelude::*;
+use serde::Deserialize;
+use tracing::instrument;
+
+use super::{ticket_booth::Routes, Player};
+use crate::{
+    animation::Animation, control::Facing, dialogue::Dialogues, inventory::ItemCatalog,
+    jobs::JobBoard,
+};
+
+/// How close, in pixels, the player has to be to talk to someone.
+const TALK_DISTANCE: f32 = 32.;
+
+/// Someone in town the player can walk up to.
+#[derive(Component, Clone, Debug)]
+pub struct Npc {
+    /// Name used to refer to this character from other data files.
+    pub id: String,
+    /// Name shown to the player.
+    pub name: String,
+    /// Dialogue tree started by talking to this character.
+    pub dialogue: Option<String>,
+}
+
+/// The character close enough to the player to talk to, if any.
+#[derive(Default, Debug)]
+pub struct NearbyNpc(pub Option<Entity>);
+
+/// Which way a character faces when spawned.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum NpcFacing {
+    Left,
+    Right,
+}
+
+impl From<NpcFacing> for Facing {
+    fn from(facing: NpcFacing) -> Self {
+        match facing {
+            NpcFacing::Left => Facing::Left,
+            NpcFacing::Right => Facing::Right,
+        }
+    }
+}
+
+/// Where a character stands and how they look.
+#[derive(Clone, Debug, Deserialize)]
+pub struct NpcSpawn {
+    pub id: String,
+    pub name: String,
+    /// Name of the tree in `assets/data/town.dialogue` to start when talked to.
+    #[serde(default)]
+    pub dialogue: Option<String>,
+    /// Position in the outside scene, in pixels.
+    pub position: (f32, f32),
+    #[serde(default = "default_facing")]
+    pub facing: NpcFacing,
+    /// Sprite sheet under `assets`, laid out like `sprites/Player.png`: a four frame walk cycle
+    /// facing right, then the same facing left, each frame 36 pixels square.
+    pub sprite: String,
+    /// The loaded sprite sheet, set by [`TownsfolkLoader`].
+    #[serde(skip)]
+    pub texture: Handle<Image>,
+    /// Half the width and height of the box the player bumps into, in pixels.
+    #[serde(default = "default_collider")]
+    pub collider: (f32, f32),
+    /// Frames of the walk cycle shuffled through while standing around.
+    #[serde(default = "default_idle_frames")]
+    pub idle_frames: usize,
+    /// Seconds each idle frame is shown for.
+    #[serde(default = "default_idle_frame_seconds")]
+    pub idle_frame_seconds: f32,
+}
+
+fn default_facing() -> NpcFacing {
+    NpcFacing::Left
+}
+
+fn default_collider() -> (f32, f32) {
+    (12.6, 18.)
+}
+
+fn default_idle_frames() -> usize {
+    2
+}
+
+fn default_idle_frame_seconds() -> f32 {
+    0.6
+}
+
+/// Everyone standing around in town.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "5b0c3f8e-2d47-4a61-9c3e-8f1d6a27e4b2"]
+pub struct Townsfolk {
+    pub npcs: Vec<NpcSpawn>,
+}
+
+#[derive(Default)]
+pub struct TownsfolkLoader;
+
+impl AssetLoader for TownsfolkLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let mut townsfolk: Townsfolk = ron::de::from_bytes(bytes)?;
+            let mut sprites = Vec::new();
+            for npc in &mut townsfolk.npcs {
+                let path = AssetPath::new(PathBuf::from(&npc.sprite), None);
+                npc.texture = load_context.get_handle(path.clone());
+                sprites.push(path);
+            }
+            load_context.set_default_asset(LoadedAsset::new(townsfolk).with_dependencies(sprites));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["npcs"]
+    }
+}
+
+#[derive(AssetCollection)]
+pub struct TownData {
+    #[asset(path = "data/townsfolk.npcs")]
+    pub townsfolk: Handle<Townsfolk>,
+    #[asset(path = "data/town.dialogue")]
+    pub dialogue: Handle<Dialogues>,
+    #[asset(path = "data/town.jobs")]
+    pub jobs: Handle<JobBoard>,
+    #[asset(path = "data/town.routes")]
+    pub routes: Handle<Routes>,
+    #[asset(path = "data/town.items")]
+    pub items: Handle<ItemCatalog>,
+}
+
+/// The town's dialogue, stagecoach routes and items, once they've loaded.
+#[derive(SystemParam)]
+pub struct Town<'w, 's> {
+    data: Res<'w, TownData>,
+    dialogues: Res<'w, Assets<Dialogues>>,
+    routes: Res<'w, Assets<Routes>>,
+    items: Res<'w, Assets<ItemCatalog>>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl Town<'_, '_> {
+    pub fn dialogues(&self) -> Option<&Dialogues> {
+        self.dialogues.get(&self.data.dialogue)
+    }
+
+    pub fn routes(&self) -> Option<&Routes> {
+        self.routes.get(&self.data.routes)
+    }
+
+    pub fn items(&self) -> Option<&ItemCatalog> {
+        self.items.get(&self.data.items)
+    }
+}
+
+#[instrument(skip(commands, town_data, townsfolk, texture_atlases, rapier_config))]
+pub fn spawn_npcs(
+    mut commands: Commands,
+    town_data: Res<TownData>,
+    townsfolk: Res<Assets<Townsfolk>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
+    rapier_config: Res<RapierConfiguration>,
+) {
+    commands.insert_resource(NearbyNpc::default());
+
+    let townsfolk = match townsfolk.get(&town_data.townsfolk) {
+        Some(townsfolk) => townsfolk,
+        None => {
+            warn!("townsfolk not loaded, town will be empty");
+            return;
+        }
+    };
+    info!(count = townsfolk.npcs.len(), "spawning townsfolk");
+
+    let scale = rapier_config.scale;
+
+    for spawn in &townsfolk.npcs {
+        let atlas = TextureAtlas::from_grid_with_padding(
+            spawn.texture.clone(),
+            Vec2::new(36., 36.),
+            4,
+            2,
+            Vec2::ONE,
+        );
+        let facing = Facing::from(spawn.facing);
+        // The left facing walk cycle starts on the second row of the sheet
+        let start_frame = match facing {
+            Facing::Right => 0,
+            Facing::Left => 4,
+        };
+        let (x, y) = spawn.position;
+        let (half_width, half_height) = spawn.collider;
+
+        commands
+            .spawn()
+            .insert(Name::new(spawn.name.clone()))
+            .insert(Npc {
+                id: spawn.id.clone(),
+                name: spawn.name.clone(),
+                dialogue: spawn.dialogue.clone(),
+            })
+            .insert(facing)
+            .insert(Animation {
+                timer: Timer::from_seconds(spawn.idle_frame_seconds, true),
+                current_frame: 0,
+                start_frame,
+                frames: spawn.idle_frames.clamp(1, 4),
+            })
+            .insert_bundle(SpriteSheetBundle {
+                texture_atlas: texture_atlases.add(atlas),
+                sprite: TextureAtlasSprite::new(start_frame),
+                transform: Transform::from_translation(Vec3::new(x, y, 1.)),
+                ..Default::default()
+            })
+            .insert_bundle(RigidBodyBundle {
+                body_type: RigidBodyType::Static.into(),
+                position: Vec2::new(x / scale, y / scale).into(),
+                ..Default::default()
+            })
+            .insert_bundle(ColliderBundle {
+                shape: ColliderShape::cuboid(half_width / scale, half_height / scale).into(),
+                material: ColliderMaterial {
+                    friction: 0.,
+                    restitution: 0.,
+                    ..Default::default()
+                }
+                .into(),
+                ..Default::default()
+            })
+            .insert(RigidBodyPositionSync::Discrete)
+            .insert(ColliderDebugRender::default());
+    }
+}
+
+/// Keeps track of the closest character within talking distance of the player.
+pub fn track_nearby_npc(
+    player: Query<&Transform, With<Player>>,
+    npcs: Query<(Entity, &Transform), With<Npc>>,
+    mut nearby: ResMut<NearbyNpc>,
+) {
+    let player = match player.get_single() {
+        Ok(player) => player.translation.truncate(),
+        Err(_) => return,
+    };
+
+    let closest = npcs
+        .iter()
+        .map(|(entity, transform)| (entity, transform.translation.truncate().distance(player)))
+        .filter(|(_, distance)| *distance <= TALK_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(entity, _)| entity);
+    if nearby.0 != closest {
+        nearby.0 = closest;
+    }
+}
@@ -0,0 +1,326 @@
+//! Ticket booth at the stagecoach stop, selling tickets for the routes in
+//! `assets/data/town.routes`, and boarding the stagecoach with one.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use serde::Deserialize;
+use tracing::instrument;
+
+use super::npc::Town;
+use crate::{
+    assets::Fonts,
+    control::InputSuspended,
+    focus::Activated,
+    inventory::Inventory,
+    save::Progress,
+    ui::{spawn_choice_button, OVERLAY_PANEL_COLOR},
+    GameState, Meowney,
+};
+
+/// Somewhere the stagecoach can take the player, ending the game.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Route {
+    pub id: String,
+    pub destination: String,
+    /// Meowney a ticket costs.
+    pub price: u32,
+}
+
+impl Route {
+    /// Id of the inventory item that lets the player take this route.
+    pub fn ticket_item(&self) -> String {
+        format!("ticket_{}", self.id)
+    }
+
+    /// Story flag set once the player has left town on this route.
+    pub fn departed_flag(&self) -> String {
+        format!("departed_{}", self.id)
+    }
+}
+
+/// Every route out of town, in the order the booth lists them.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "9e2d5c71-3a84-4f0b-b6e1-7c4a0f28d95e"]
+pub struct Routes {
+    pub routes: Vec<Route>,
+}
+
+impl Routes {
+    /// Routes the player holds a ticket for, with their index, in the order the booth lists them.
+    pub fn tickets_held(&self, inventory: &Inventory) -> Vec<(usize, &Route)> {
+        self.routes
+            .iter()
+            .enumerate()
+            .filter(|(_, route)| inventory.has(&route.ticket_item()))
+            .collect()
+    }
+}
+
+/// Hands in the ticket for a route and leaves town on the stagecoach.
+pub fn depart(
+    route: &Route,
+    inventory: &mut Inventory,
+    progress: &mut Progress,
+    app_state: &mut State<GameState>,
+) {
+    info!(route = %route.id, "transitioning to end scene");
+    inventory.remove(&route.ticket_item(), 1);
+    progress.flags.insert(route.departed_flag());
+    app_state.set(GameState::End).unwrap();
+}
+
+#[derive(Default)]
+pub struct RoutesLoader;
+
+impl AssetLoader for RoutesLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let routes: Routes = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(routes));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["routes"]
+    }
+}
+
+/// Present while the ticket booth is open.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TicketBooth {
+    /// Buying tickets from the ticket agent.
+    Selling,
+    /// Picking which ticket to ride the stagecoach with, when the player holds more than one.
+    Boarding,
+}
+
+impl TicketBooth {
+    /// Opens the booth, suspending player input until it closes.
+    pub fn open(self, commands: &mut Commands) {
+        commands.insert_resource(self);
+        commands.insert_resource(InputSuspended);
+    }
+
+    pub fn close(commands: &mut Commands) {
+        commands.remove_resource::<TicketBooth>();
+        commands.remove_resource::<InputSuspended>();
+    }
+}
+
+#[derive(Component)]
+pub struct TicketBoothPanel;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoothChoice {
+    /// Buy a ticket for the route at this index.
+    Buy(usize),
+    /// Ride the stagecoach on the route at this index.
+    Board(usize),
+    Close,
+}
+
+/// Opens the booth panel when the booth opens, and redraws it after a purchase.
+#[instrument(skip(commands, booth, panels, town, fonts, meowney, inventory))]
+pub fn show_booth(
+    mut commands: Commands,
+    booth: Option<Res<TicketBooth>>,
+    panels: Query<Entity, With<TicketBoothPanel>>,
+    town: Town,
+    fonts: Res<Fonts>,
+    meowney: Res<Meowney>,
+    inventory: Res<Inventory>,
+) {
+    let booth = match booth {
+        Some(booth) => booth,
+        None => {
+            panels.for_each(|entity| commands.entity(entity).despawn_recursive());
+            return;
+        }
+    };
+    if !booth.is_changed() && !inventory.is_changed() {
+        return;
+    }
+    panels.for_each(|entity| commands.entity(entity).despawn_recursive());
+
+    let routes = match town.routes() {
+        Some(routes) => routes,
+        None => return,
+    };
+
+    let text_style = |color| TextStyle {
+        font: fonts.vt323.clone(),
+        font_size: 36.,
+        color,
+    };
+    let (title, mut choices) = match *booth {
+        TicketBooth::Selling => (
+            "Stagecoach tickets",
+            routes
+                .routes
+                .iter()
+                .enumerate()
+                .map(|(index, route)| {
+                    if inventory.has(&route.ticket_item()) {
+                        (
+                            format!("{}: ticket bought", route.destination),
+                            BoothChoice::Buy(index),
+                            false,
+                        )
+                    } else {
+                        (
+                            format!("{}: {} Meowney", route.destination, route.price),
+                            BoothChoice::Buy(index),
+                            meowney.0 >= route.price,
+                        )
+                    }
+                })
+                .collect::<Vec<_>>(),
+        ),
+        TicketBooth::Boarding => (
+            "Where to?",
+            routes
+                .tickets_held(&inventory)
+                .into_iter()
+                .map(|(index, route)| {
+                    (
+                        format!("Ride to {}", route.destination),
+                        BoothChoice::Board(index),
+                        true,
+                    )
+                })
+                .collect(),
+        ),
+    };
+    let close = match *booth {
+        TicketBooth::Selling => "Close",
+        TicketBooth::Boarding => "Not yet",
+    };
+    choices.push((close.to_string(), BoothChoice::Close, true));
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    left: Val::Percent(25.),
+                    top: Val::Percent(20.),
+                    ..Default::default()
+                },
+                size: Size {
+                    width: Val::Percent(50.),
+                    height: Val::Auto,
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                padding: Rect::all(Val::Px(16.)),
+                ..Default::default()
+            },
+            color: OVERLAY_PANEL_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(Name::new("Ticket Booth"))
+        .insert(TicketBoothPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(16.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    title,
+                    TextStyle {
+                        font_size: 48.,
+                        ..text_style(Color::WHITE)
+                    },
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            for (index, (label, choice, available)) in choices.into_iter().enumerate() {
+                spawn_choice_button(
+                    parent,
+                    index,
+                    label,
+                    text_style(Color::WHITE),
+                    available,
+                    choice,
+                );
+            }
+        });
+}
+
+/// Sells the activated ticket, boards the stagecoach, or closes the booth.
+#[allow(clippy::too_many_arguments)]
+#[instrument(skip(
+    commands, activated, buttons, town, meowney, inventory, progress, app_state
+))]
+pub fn choose(
+    mut commands: Commands,
+    mut activated: EventReader<Activated>,
+    buttons: Query<&BoothChoice>,
+    town: Town,
+    mut meowney: ResMut<Meowney>,
+    mut inventory: ResMut<Inventory>,
+    mut progress: ResMut<Progress>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    for choice in activated
+        .iter()
+        .filter_map(|Activated(entity)| buttons.get(*entity).ok())
+    {
+        match choice {
+            BoothChoice::Buy(index) => {
+                let (route, items) = match (
+                    town.routes().and_then(|routes| routes.routes.get(*index)),
+                    town.items(),
+                ) {
+                    (Some(route), Some(items)) => (route, items),
+                    _ => return,
+                };
+                if meowney.0 < route.price {
+                    warn!(route = %route.id, "player does not have enough meowney");
+                    return;
+                }
+                if !inventory.can_add(items, &route.ticket_item(), 1) {
+                    warn!(route = %route.id, "player can't carry another ticket");
+                    return;
+                }
+
+                info!(route = %route.id, price = route.price, "bought stagecoach ticket");
+                meowney.0 -= route.price;
+                inventory.add(items, &route.ticket_item(), 1);
+            }
+            BoothChoice::Board(index) => {
+                let route = match town.routes().and_then(|routes| routes.routes.get(*index)) {
+                    Some(route) => route,
+                    None => return,
+                };
+
+                TicketBooth::close(&mut commands);
+                depart(route, &mut inventory, &mut progress, &mut app_state);
+            }
+            BoothChoice::Close => {
+                info!("closing ticket booth");
+                TicketBooth::close(&mut commands);
+            }
+        }
+
+        return;
+    }
+}
+
+#[instrument(skip(commands, panels))]
+pub fn teardown(mut commands: Commands, panels: Query<Entity, With<TicketBoothPanel>>) {
+    panels.for_each(|entity| commands.entity(entity).despawn_recursive());
+    TicketBooth::close(&mut commands);
+}
@@ -0,0 +1,188 @@
+//! Pause overlay shown on top of the outside and post office scenes.
+
+use bevy::{app::AppExit, prelude::*};
+use bevy_rapier2d::prelude::*;
+use tracing::instrument;
+
+use crate::{
+    action::Action,
+    assets::Fonts,
+    focus::{Activated, Focusable},
+    scene::post_office::autopilot::Autopilot,
+    ui::BUTTON_COLOR,
+    GameState,
+};
+
+#[derive(Component)]
+pub struct PauseMenu;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PauseItem {
+    Resume,
+    Settings,
+    MainMenu,
+    Quit,
+}
+
+impl PauseItem {
+    const ALL: [PauseItem; 4] = [
+        PauseItem::Resume,
+        PauseItem::Settings,
+        PauseItem::MainMenu,
+        PauseItem::Quit,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PauseItem::Resume => "Resume",
+            PauseItem::Settings => "Settings",
+            PauseItem::MainMenu => "Return to Main Menu",
+            PauseItem::Quit => "Quit",
+        }
+    }
+}
+
+/// Opens the pause overlay, or closes whichever overlay is on top, when the pause action is
+/// pressed.
+///
+/// This runs once a frame outside the state-driven stage, so the press that opens the overlay
+/// isn't seen again by the overlay and used to close it straight away.
+#[instrument(skip(actions, autopilot, app_state))]
+pub fn toggle_pause(
+    actions: Res<Input<Action>>,
+    autopilot: Option<Res<Autopilot>>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    // Any input ends attract mode instead
+    if !actions.just_pressed(Action::Pause) || autopilot.is_some() {
+        return;
+    }
+
+    let current = app_state.current().clone();
+    let result = match current {
+        state if state.is_pausable() => {
+            info!(?state, "pausing");
+            app_state.push(GameState::Paused)
+        }
+        GameState::Paused | GameState::Settings => app_state.pop(),
+        _ => return,
+    };
+    if let Err(error) = result {
+        warn!(?error, "failed to toggle pause");
+    }
+}
+
+#[instrument(skip(rapier_config))]
+pub fn freeze_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = false;
+}
+
+#[instrument(skip(rapier_config))]
+pub fn unfreeze_physics(mut rapier_config: ResMut<RapierConfiguration>) {
+    rapier_config.physics_pipeline_active = true;
+}
+
+#[instrument(skip(commands, fonts))]
+pub fn setup(mut commands: Commands, fonts: Res<Fonts>) {
+    info!("setting up pause menu");
+
+    let text_style = |font_size| TextStyle {
+        font: fonts.vt323.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: Color::rgba(0., 0., 0., 0.6).into(),
+            ..Default::default()
+        })
+        .insert(Name::new("Pause Menu"))
+        .insert(PauseMenu)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(24.)),
+                    ..Default::default()
+                },
+                text: Text::with_section("Paused", text_style(64.), Default::default()),
+                ..Default::default()
+            });
+
+            for (index, item) in PauseItem::ALL.into_iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(8.)),
+                            padding: Rect::all(Val::Px(8.)),
+                            ..Default::default()
+                        },
+                        color: BUTTON_COLOR.into(),
+                        ..Default::default()
+                    })
+                    .insert(item)
+                    .insert(Focusable(index))
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(
+                                item.label(),
+                                text_style(36.),
+                                Default::default(),
+                            ),
+                            ..Default::default()
+                        });
+                    });
+            }
+        });
+}
+
+#[instrument(skip(activated, query, app_state, exit))]
+pub fn click_item(
+    mut activated: EventReader<Activated>,
+    query: Query<&PauseItem>,
+    mut app_state: ResMut<State<GameState>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    for item in activated
+        .iter()
+        .filter_map(|Activated(entity)| query.get(*entity).ok())
+    {
+        match item {
+            PauseItem::Resume => {
+                info!("resuming");
+                app_state.pop().unwrap();
+            }
+            PauseItem::Settings => {
+                info!("opening settings");
+                app_state.push(GameState::Settings).unwrap();
+            }
+            PauseItem::MainMenu => {
+                info!("returning to main menu");
+                app_state.replace(GameState::MainMenu).unwrap();
+            }
+            PauseItem::Quit => {
+                info!("quitting");
+                exit.send(AppExit);
+            }
+        }
+
+        return;
+    }
+}
+
+#[instrument(skip(commands, query))]
+pub fn teardown(mut commands: Commands, query: Query<Entity, With<PauseMenu>>) {
+    info!("tearing down pause menu");
+    query.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
@@ -1,296 +1,711 @@
-use bevy::prelude::*;
+use std::{
+    collections::VecDeque,
+    f32::consts::{FRAC_PI_2, PI},
+    marker::PhantomData,
+    time::Duration,
+};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
 use rand::Rng;
 use tracing::instrument;
 
-use crate::{assets::Images, GameState, Meowney};
+use self::{
+    autopilot::Autopilot,
+    difficulty::{CustomDifficulty, SelectedArenaMode, SelectedDifficulty, SnakeConfig},
+    layout::{ArenaLayout, Layouts, SelectedLayout},
+    results::SnakeResults,
+    snake::{self, Direction, FoodKind, GameOver, Position, SnakeGame},
+};
+use crate::{
+    action::Action,
+    assets::Images,
+    high_scores::{self, ArenaKind, HighScore, HighScores},
+    jobs::ShiftFinished,
+    replay::Replayer,
+    rng::GameRng,
+    settings::Settings,
+    GameState, Meowney,
+};
+
+pub mod autopilot;
+pub mod difficulty;
+pub mod layout;
+pub mod prompt;
+pub mod results;
+pub mod snake;
+
+/// Size of a cell, in pixels, that the snake sprites are drawn for.
+const SPRITE_CELL_SIZE: f32 = 24.;
+/// Index of the head in `images/snake.png`. Every snake piece is drawn as if heading up.
+const HEAD_SPRITE: usize = 0;
+/// Index of the body piece joining the cells above and below.
+const STRAIGHT_SPRITE: usize = 1;
+/// Index of the body piece joining the cells below and to the right.
+const CORNER_SPRITE: usize = 2;
+/// Index of the tail tip, joining the cell above.
+const TAIL_SPRITE: usize = 3;
+/// Width and height, in pixels, of the sandy square in the middle of the background.
+const BACKGROUND_ARENA_SIZE: f32 = 264.;
+
+/// How large each arena cell is drawn, so that the whole arena fits on the background.
+#[derive(Debug)]
+pub struct ArenaScale {
+    pub cell_size: f32,
+}
 
-const ARENA_WIDTH: i32 = 5;
-const ARENA_HEIGHT: i32 = 5;
+impl ArenaScale {
+    fn fit(config: &SnakeConfig) -> Self {
+        let border = if config.mode.shows_border() { 2 } else { 0 };
+        let cells = (config.arena_width().max(config.arena_height()) + border) as f32;
+        Self {
+            cell_size: (BACKGROUND_ARENA_SIZE / cells).min(SPRITE_CELL_SIZE),
+        }
+    }
+}
 
+/// The cell an entity occupies in the snake arena.
 #[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
-pub struct Position {
-    x: i32,
-    y: i32,
-}
+pub struct GridPosition(pub Position);
+
+/// The cell a snake segment occupied before its last move, for smooth movement.
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PreviousGridPosition(pub Position);
 
 #[derive(Component)]
 pub struct MoveTimer(pub Timer);
 
-#[derive(Component, Clone, Copy, PartialEq, Eq)]
+/// The state of the current game of snake.
+pub struct Snake(pub SnakeGame);
+
+/// Most turns that can be queued up between two moves.
+const MAX_PENDING_TURNS: usize = 3;
+
+#[derive(Component, Clone, PartialEq, Eq)]
 pub struct SnakeHead {
-    direction: Direction,
+    /// Direction the snake took on its last move.
+    pub direction: Direction,
+    /// Turns to take on the next moves, one per move.
+    pub pending_turns: VecDeque<Direction>,
+}
+
+impl SnakeHead {
+    pub fn new(direction: Direction) -> Self {
+        Self {
+            direction,
+            pending_turns: VecDeque::new(),
+        }
+    }
+
+    /// Queues a turn, unless it wouldn't change direction, would reverse into the neck, or the
+    /// queue is full.
+    pub fn queue_turn(&mut self, direction: Direction) -> bool {
+        let last = self.pending_turns.back().copied().unwrap_or(self.direction);
+        if direction == last
+            || direction == last.opposite()
+            || self.pending_turns.len() >= MAX_PENDING_TURNS
+        {
+            return false;
+        }
+
+        self.pending_turns.push_back(direction);
+        true
+    }
 }
 
 #[derive(Component, Clone, Copy, PartialEq, Eq)]
 pub struct SnakeSegment;
 
-#[derive(Default)]
-pub struct SnakeSegments(Vec<Entity>);
+/// Texture atlas of the snake pieces.
+pub struct SnakeAtlas(pub Handle<TextureAtlas>);
 
+/// Segment entities, in the same order as [`SnakeGame::body`].
 #[derive(Default)]
-pub struct LastTailPosition(Option<Position>);
-
-#[derive(Clone, Copy, PartialEq, Eq)]
-pub enum Direction {
-    Left,
-    Down,
-    Up,
-    Right,
-}
-
-impl Direction {
-    fn opposite(self) -> Self {
-        match self {
-            Direction::Left => Self::Right,
-            Direction::Down => Self::Up,
-            Direction::Up => Self::Down,
-            Direction::Right => Self::Left,
-        }
-    }
-}
+pub struct SnakeSegments(Vec<Entity>);
 
 pub struct SnakeGrowth;
 
-pub struct SpawnFood;
+/// Sent when food is placed in the arena, so its sprite can be spawned.
+pub struct SpawnFood(pub snake::Food);
 
-pub struct SnakeGameOver;
+pub struct SnakeGameOver(pub GameOver);
 
 #[derive(Component, Clone, Copy, PartialEq, Eq)]
 pub struct Background;
 
-#[instrument(skip(commands, images, segments, spawn_writer))]
+/// What the player picked on the prompt before the shift.
+#[derive(SystemParam)]
+pub struct ShiftChoices<'w, 's> {
+    difficulty: Res<'w, SelectedDifficulty>,
+    mode: Res<'w, SelectedArenaMode>,
+    custom: Res<'w, CustomDifficulty>,
+    layouts: Res<'w, Layouts>,
+    layout_assets: Res<'w, Assets<ArenaLayout>>,
+    layout: Res<'w, SelectedLayout>,
+    #[system_param(ignore)]
+    marker: PhantomData<&'s ()>,
+}
+
+impl ShiftChoices<'_, '_> {
+    fn config(&self) -> SnakeConfig {
+        self.difficulty.0.config(&self.custom, self.mode.0)
+    }
+
+    /// The picked arena layout, if it's loaded.
+    fn layout(&self) -> Option<&ArenaLayout> {
+        self.layouts
+            .all()
+            .get(self.layout.0)
+            .and_then(|handle| self.layout_assets.get(*handle))
+    }
+}
+
+#[instrument(skip(
+    commands,
+    images,
+    texture_atlases,
+    segments,
+    spawn_writer,
+    choices,
+    rng
+))]
 pub fn setup(
     mut commands: Commands,
     images: Res<Images>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
     mut segments: ResMut<SnakeSegments>,
     mut spawn_writer: EventWriter<SpawnFood>,
+    choices: ShiftChoices,
+    mut rng: ResMut<GameRng>,
 ) {
     info!("setting up post office scene");
 
-    commands.insert_resource(MoveTimer(Timer::from_seconds(0.2, true)));
+    let config = choices.config();
+    info!(difficulty = ?choices.difficulty.0, ?config, "starting snake");
+
+    commands.insert_resource(MoveTimer(Timer::from_seconds(config.tick_seconds, true)));
+
+    let scale = ArenaScale::fit(&config);
+    let arena_size =
+        Vec2::new(config.arena_width() as f32, config.arena_height() as f32) * scale.cell_size;
 
     commands
         .spawn()
         .insert_bundle(SpriteBundle {
-            transform: Transform::from_translation(Vec3::new(0., 0., 0.)),
+            transform: Transform {
+                translation: Vec3::new(0., 0., 0.),
+                scale: (arena_size / BACKGROUND_ARENA_SIZE).extend(1.),
+                ..Default::default()
+            },
             texture: images.snakebackground.clone(),
             ..Default::default()
         })
         .insert(Name::new("Snake Background"))
         .insert(Background);
 
-    segments.0 = vec![
-        commands
-            .spawn()
-            .insert(Name::new("Snake Head"))
-            .insert(SnakeHead {
-                direction: Direction::Up,
-            })
-            .insert(SnakeSegment)
-            .insert(Position { x: 3, y: 2 })
-            .insert_bundle(SpriteBundle {
-                texture: images.head.clone(),
-                transform: Transform::from_translation(Vec3::new(0., 0., 2.)),
-                ..Default::default()
-            })
-            .id(),
-        spawn_segment(commands, Position { x: 3, y: 2 }, images),
-    ];
+    if config.mode.shows_border() {
+        let border_atlas = TextureAtlas::from_grid_with_padding(
+            images.border.clone(),
+            Vec2::new(SPRITE_CELL_SIZE, SPRITE_CELL_SIZE),
+            2,
+            1,
+            Vec2::ONE,
+        );
+        let border_atlas_handle = texture_atlases.add(border_atlas);
+        for position in config.arena().border() {
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: border_atlas_handle.clone(),
+                    sprite: TextureAtlasSprite::new(0),
+                    transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
+                    ..Default::default()
+                })
+                .insert(GridPosition(position))
+                .insert(Background);
+        }
+    }
+
+    let layout = choices.layout();
+    let arena = config.arena();
+    // Each run gets its own seed, recorded with its high score so it can be played again
+    let seed: u64 = rng.gen();
+    info!(seed, "seeded snake");
+    let mut game = match layout {
+        Some(layout) => {
+            info!(layout = %layout.name, "using arena layout");
+            let start = arena.clamp(layout.start);
+            SnakeGame::new(arena, start, layout.heading, 2, seed).with_obstacles(
+                layout
+                    .obstacle_cells()
+                    .map(|(cell, _)| cell)
+                    .filter(|cell| *cell != start),
+            )
+        }
+        None => {
+            warn!(index = choices.layout.0, "arena layout not loaded");
+            SnakeGame::new(
+                arena,
+                arena.clamp(Position { x: 3, y: 2 }),
+                Direction::Up,
+                2,
+                seed,
+            )
+        }
+    };
+    commands.insert_resource(ArenaKind {
+        mode: config.mode,
+        layout: layout.map(|layout| layout.name.clone()).unwrap_or_default(),
+    });
+
+    if let Some(layout) = layout {
+        let obstacle_atlas = TextureAtlas::from_grid_with_padding(
+            images.obstacles.clone(),
+            Vec2::new(SPRITE_CELL_SIZE, SPRITE_CELL_SIZE),
+            3,
+            1,
+            Vec2::ONE,
+        );
+        let obstacle_atlas_handle = texture_atlases.add(obstacle_atlas);
+
+        for (position, kind) in layout.obstacle_cells() {
+            if !game.obstacles().any(|cell| cell == position) {
+                continue;
+            }
+            commands
+                .spawn_bundle(SpriteSheetBundle {
+                    texture_atlas: obstacle_atlas_handle.clone(),
+                    sprite: TextureAtlasSprite::new(kind.sprite_index()),
+                    transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
+                    ..Default::default()
+                })
+                .insert(GridPosition(position))
+                .insert(Background);
+        }
+    }
+
+    let snake_atlas = SnakeAtlas(texture_atlases.add(TextureAtlas::from_grid_with_padding(
+        images.snake.clone(),
+        Vec2::new(SPRITE_CELL_SIZE, SPRITE_CELL_SIZE),
+        4,
+        1,
+        Vec2::ONE,
+    )));
+
+    segments.0 = game
+        .body()
+        .enumerate()
+        .map(|(index, position)| {
+            let segment = spawn_segment(&mut commands, position, &snake_atlas);
+            if index == 0 {
+                commands
+                    .entity(segment)
+                    .insert(Name::new("Snake Head"))
+                    .insert(SnakeHead::new(game.heading()));
+            }
+            segment
+        })
+        .collect();
 
-    info!("sending spawn food event");
-    spawn_writer.send(SpawnFood);
+    // Every arena has room for the first piece of food, and if it somehow doesn't, the first
+    // step ends the game
+    if let Ok(food) = game.spawn_food() {
+        spawn_writer.send(SpawnFood(food));
+    }
+
+    commands.insert_resource(Snake(game));
+    commands.insert_resource(snake_atlas);
+    commands.insert_resource(scale);
+    commands.insert_resource(config);
 }
 
 pub fn update_head_direction(
     mut query: Query<&mut SnakeHead>,
-    keyboard_input: Res<Input<KeyCode>>,
+    actions: Res<Input<Action>>,
+    replayer: Option<Res<Replayer>>,
+    autopilot: Option<Res<Autopilot>>,
 ) {
-    for mut head in query.iter_mut() {
-        let dir = if keyboard_input.pressed(KeyCode::A) {
-            Direction::Left
-        } else if keyboard_input.pressed(KeyCode::S) {
-            Direction::Down
-        } else if keyboard_input.pressed(KeyCode::W) {
-            Direction::Up
-        } else if keyboard_input.pressed(KeyCode::D) {
-            Direction::Right
-        } else {
-            head.direction
-        };
+    if replayer.is_some() || autopilot.is_some() {
+        return;
+    }
+
+    let turns = [
+        (Action::MoveLeft, Direction::Left),
+        (Action::MoveDown, Direction::Down),
+        (Action::MoveUp, Direction::Up),
+        (Action::MoveRight, Direction::Right),
+    ];
 
-        if dir != head.direction.opposite() {
-            head.direction = dir;
+    for mut head in query.iter_mut() {
+        for (action, direction) in turns {
+            if actions.just_pressed(action) && head.queue_turn(direction) {
+                debug!(?direction, "turn queued");
+            }
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn move_snake(
-    segments: ResMut<SnakeSegments>,
-    mut last_tail_position: ResMut<LastTailPosition>,
+    mut snake: ResMut<Snake>,
+    segments: Res<SnakeSegments>,
+    mut growth_writer: EventWriter<SnakeGrowth>,
+    mut spawn_writer: EventWriter<SpawnFood>,
     mut game_over_writer: EventWriter<SnakeGameOver>,
-    mut heads: Query<(Entity, &SnakeHead)>,
-    mut positions: Query<&mut Position>,
+    mut heads: Query<&mut SnakeHead>,
+    mut positions: Query<(&mut GridPosition, &mut PreviousGridPosition)>,
     mut move_timer: ResMut<MoveTimer>,
     time: Res<Time>,
 ) {
     move_timer.0.tick(time.delta());
 
-    if move_timer.0.finished() {
-        if let Some((head_entity, head)) = heads.iter_mut().next() {
-            let segment_positions = segments
-                .0
-                .iter()
-                .map(|e| *positions.get_mut(*e).unwrap())
-                .collect::<Vec<_>>();
-            let mut head_pos = positions.get_mut(head_entity).unwrap();
-
-            match &head.direction {
-                Direction::Left => head_pos.x -= 1,
-                Direction::Down => head_pos.y -= 1,
-                Direction::Up => head_pos.y += 1,
-                Direction::Right => head_pos.x += 1,
-            };
-
-            if head_pos.x < -ARENA_WIDTH
-                || head_pos.y < -ARENA_HEIGHT
-                || head_pos.x > ARENA_WIDTH
-                || head_pos.y > ARENA_HEIGHT
-            {
-                warn!(?head_pos, "game over, snake hit side of arena");
-                game_over_writer.send(SnakeGameOver);
+    if !move_timer.0.finished() {
+        return;
+    }
+
+    if let Some(mut head) = heads.iter_mut().next() {
+        if let Some(turn) = head.pending_turns.pop_front() {
+            snake.0.turn(turn);
+        }
+        head.direction = snake.0.heading();
+    }
+
+    match snake.0.step() {
+        Ok(step) => {
+            for (entity, position) in segments.0.iter().zip(snake.0.body()) {
+                let (mut current, mut previous) = positions.get_mut(*entity).unwrap();
+                previous.0 = current.0;
+                current.0 = position;
             }
 
-            if segment_positions.contains(&head_pos) {
-                warn!(?head_pos, "game over, snake hit own tail");
-                game_over_writer.send(SnakeGameOver);
+            if step.grew {
+                growth_writer.send(SnakeGrowth);
+            }
+            if let Some(food) = step.spawned {
+                spawn_writer.send(SpawnFood(food));
+            }
+        }
+        Err(game_over) => {
+            // Settle the snake where it is rather than sliding it towards what it hit
+            for entity in &segments.0 {
+                let (current, mut previous) = positions.get_mut(*entity).unwrap();
+                previous.0 = current.0;
             }
 
-            segment_positions
-                .iter()
-                .zip(segments.0.iter().skip(1))
-                .for_each(|(pos, segment)| {
-                    *positions.get_mut(*segment).unwrap() = *pos;
-                });
-            last_tail_position.0 = Some(*segment_positions.last().unwrap());
+            let head_pos = snake.0.head().step(snake.0.heading());
+            match game_over {
+                GameOver::HitWall => warn!(?head_pos, "game over, snake hit side of arena"),
+                GameOver::HitOwnTail => warn!(?head_pos, "game over, snake hit own tail"),
+                GameOver::HitObstacle => warn!(?head_pos, "game over, snake hit an obstacle"),
+                GameOver::BoardCleared => info!("game over, board cleared"),
+            }
+            game_over_writer.send(SnakeGameOver(game_over));
         }
     }
 }
 
-fn spawn_segment(mut commands: Commands, position: Position, images: Res<Images>) -> Entity {
+fn spawn_segment(commands: &mut Commands, position: Position, atlas: &SnakeAtlas) -> Entity {
     commands
-        .spawn_bundle(SpriteBundle {
-            texture: images.tail.clone(),
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas: atlas.0.clone(),
+            sprite: TextureAtlasSprite::new(STRAIGHT_SPRITE),
             transform: Transform::from_translation(Vec3::new(0., 0., 2.)),
             ..Default::default()
         })
         .insert(SnakeSegment)
-        .insert(position)
+        .insert(GridPosition(position))
+        .insert(PreviousGridPosition(position))
         .id()
 }
 
 #[derive(Component)]
 pub struct Food;
 
+/// Time left before a piece of food disappears, for kinds that don't stay put forever.
+#[derive(Component)]
+pub struct Expiry(pub Timer);
+
+fn food_texture(images: &Images, kind: FoodKind) -> Handle<Image> {
+    match kind {
+        FoodKind::Letter => images.letter.clone(),
+        FoodKind::Parcel => images.parcel.clone(),
+        FoodKind::Express => images.express.clone(),
+    }
+}
+
+/// Despawns food the snake has eaten.
 pub fn snake_eating(
     mut commands: Commands,
-    mut growth_writer: EventWriter<SnakeGrowth>,
-    food_positions: Query<(Entity, &Position), With<Food>>,
-    head_positions: Query<&Position, With<SnakeHead>>,
+    snake: Res<Snake>,
+    food_positions: Query<(Entity, &GridPosition), With<Food>>,
 ) {
-    for head_pos in head_positions.iter() {
-        for (ent, food_pos) in food_positions.iter() {
-            if food_pos == head_pos {
-                commands.entity(ent).despawn();
-                growth_writer.send(SnakeGrowth);
-            }
+    for (ent, food_pos) in food_positions.iter() {
+        if !snake.0.has_food_at(food_pos.0) {
+            commands.entity(ent).despawn();
         }
     }
 }
 
 pub fn snake_growth(
-    commands: Commands,
-    last_tail_position: Res<LastTailPosition>,
+    mut commands: Commands,
+    snake: Res<Snake>,
+    config: Res<SnakeConfig>,
     mut segments: ResMut<SnakeSegments>,
+    mut move_timer: ResMut<MoveTimer>,
     mut growth_reader: EventReader<SnakeGrowth>,
-    mut spawn_writer: EventWriter<SpawnFood>,
-    images: Res<Images>,
+    atlas: Res<SnakeAtlas>,
 ) {
     if growth_reader.iter().next().is_some() {
-        segments.0.push(spawn_segment(
-            commands,
-            last_tail_position.0.unwrap(),
-            images,
-        ));
-        spawn_writer.send(SpawnFood);
+        segments
+            .0
+            .push(spawn_segment(&mut commands, snake.0.tail(), &atlas));
+
+        let tick_seconds = config.tick_seconds_after(snake.0.segments_added());
+        debug!(tick_seconds, "snake sped up");
+        move_timer
+            .0
+            .set_duration(Duration::from_secs_f32(tick_seconds));
     }
 }
 
-#[instrument(skip(commands, images, spawn_reader))]
+/// Spawns sprites for food placed in the arena.
 pub fn food_spawner(
     mut commands: Commands,
     images: Res<Images>,
     mut spawn_reader: EventReader<SpawnFood>,
 ) {
-    if spawn_reader.iter().next().is_some() {
-        info!("spawning food");
+    for SpawnFood(food) in spawn_reader.iter() {
+        debug!(?food, "spawning food");
 
-        let mut rng = rand::thread_rng();
-        let x = rng.gen_range(-ARENA_WIDTH..=ARENA_WIDTH);
-        let y = rng.gen_range(-ARENA_HEIGHT..=ARENA_HEIGHT);
+        let mut entity = commands.spawn_bundle(SpriteBundle {
+            texture: food_texture(&images, food.kind),
+            transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
+            ..Default::default()
+        });
+        entity.insert(Food).insert(GridPosition(food.position));
+        if let Some(seconds) = food.kind.lifetime_seconds() {
+            entity.insert(Expiry(Timer::from_seconds(seconds, false)));
+        }
+    }
+}
 
-        let position = Position { x, y };
+/// Takes away food that wasn't picked up in time, and places a replacement. A replay takes away
+/// food when it was recorded to expire instead.
+pub fn expire_food(
+    mut commands: Commands,
+    mut snake: ResMut<Snake>,
+    mut spawn_writer: EventWriter<SpawnFood>,
+    mut food: Query<(Entity, &GridPosition, &mut Expiry), With<Food>>,
+    replayer: Option<Res<Replayer>>,
+    time: Res<Time>,
+) {
+    if replayer.is_some() {
+        return;
+    }
 
-        debug!(?position, "spawning food");
+    for (entity, position, mut expiry) in food.iter_mut() {
+        // Food eaten this frame is despawned by `snake_eating`
+        if !expiry.0.tick(time.delta()).finished() || !snake.0.has_food_at(position.0) {
+            continue;
+        }
 
-        commands
-            .spawn_bundle(SpriteBundle {
-                texture: images.letter.clone(),
-                transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
-                ..Default::default()
-            })
-            .insert(Food)
-            .insert(position);
+        debug!(position = ?position.0, "food expired");
+        // Despawn this sprite rather than whatever is on its cell, as the replacement may land
+        // in the same place
+        commands.entity(entity).despawn();
+        if let Some(replacement) = snake.0.expire_food(position.0) {
+            spawn_writer.send(SpawnFood(replacement));
+        }
     }
 }
 
-pub fn position_translation(mut q: Query<(&Position, &mut Transform)>) {
-    let scale = 24.;
+/// Places sprites on their grid cells. With smooth movement on, snake segments slide from their
+/// previous cell to their current one as the move timer runs, instead of jumping.
+pub fn position_translation(
+    mut q: Query<(&GridPosition, Option<&PreviousGridPosition>, &mut Transform)>,
+    arena_scale: Option<Res<ArenaScale>>,
+    settings: Res<Settings>,
+    move_timer: Option<Res<MoveTimer>>,
+) {
+    // There's only an arena to place sprites in during a shift
+    let scale = match arena_scale {
+        Some(arena_scale) => arena_scale.cell_size,
+        None => return,
+    };
+    let progress = move_timer
+        .filter(|_| settings.smooth_movement)
+        .map(|move_timer| move_timer.0.percent());
+
+    for (pos, previous, mut transform) in q.iter_mut() {
+        let current = Vec2::new(pos.0.x as f32, pos.0.y as f32);
+        let cell = match (progress, previous) {
+            (Some(progress), Some(previous)) => {
+                let previous = Vec2::new(previous.0.x as f32, previous.0.y as f32);
+                // Wrapping around the arena is more than one cell, so jump rather than sweep
+                // across the whole arena
+                if previous.distance_squared(current) > 1. {
+                    current
+                } else {
+                    previous.lerp(current, progress)
+                }
+            }
+            _ => current,
+        };
 
-    for (pos, mut transform) in q.iter_mut() {
-        transform.translation = Vec3::new(
-            pos.x as f32 * scale,
-            pos.y as f32 * scale,
-            transform.translation.z,
-        );
+        transform.translation = (cell * scale).extend(transform.translation.z);
+        transform.scale = Vec3::splat(scale / SPRITE_CELL_SIZE);
+    }
+}
+
+/// Rotation that turns a snake piece drawn heading up to face `direction`.
+fn facing(direction: Direction) -> Quat {
+    Quat::from_rotation_z(match direction {
+        Direction::Up => 0.,
+        Direction::Left => FRAC_PI_2,
+        Direction::Down => PI,
+        Direction::Right => -FRAC_PI_2,
+    })
+}
+
+/// Sprite and rotation for a body segment joining the cells in directions `a` and `b`.
+fn body_piece(a: Direction, b: Direction) -> (usize, Quat) {
+    if a == b.opposite() {
+        return (STRAIGHT_SPRITE, facing(a));
+    }
+
+    // Find the quarter turn that brings the corner's down and right ends round to `a` and `b`
+    let turn_left = |direction: Direction| match direction {
+        Direction::Up => Direction::Left,
+        Direction::Left => Direction::Down,
+        Direction::Down => Direction::Right,
+        Direction::Right => Direction::Up,
+    };
+    let (mut down, mut right) = (Direction::Down, Direction::Right);
+    for quarter_turns in 0..4 {
+        if (down == a && right == b) || (down == b && right == a) {
+            return (
+                CORNER_SPRITE,
+                Quat::from_rotation_z(quarter_turns as f32 * FRAC_PI_2),
+            );
+        }
+        down = turn_left(down);
+        right = turn_left(right);
+    }
+
+    (STRAIGHT_SPRITE, facing(a))
+}
+
+/// Picks the head, body, corner or tail sprite for each segment from its neighbours.
+pub fn update_snake_sprites(
+    snake: Option<Res<Snake>>,
+    segments: Res<SnakeSegments>,
+    mut sprites: Query<(&mut TextureAtlasSprite, &mut Transform), With<SnakeSegment>>,
+) {
+    let snake = match snake {
+        Some(snake) if snake.is_changed() || segments.is_changed() => snake,
+        _ => return,
+    };
+
+    let body = snake.0.body().collect::<Vec<_>>();
+    // Segments start out stacked on one cell, so fall back to the heading until they spread out
+    let towards = |from: usize, to: usize| {
+        Direction::ALL
+            .into_iter()
+            .find(|direction| snake.0.arena().neighbour(body[from], *direction) == Some(body[to]))
+    };
+
+    for (index, entity) in segments.0.iter().enumerate() {
+        let (mut sprite, mut transform) = match sprites.get_mut(*entity) {
+            Ok(sprite) => sprite,
+            Err(_) => continue,
+        };
+
+        let ahead = (index > 0)
+            .then(|| towards(index, index - 1))
+            .flatten()
+            .unwrap_or_else(|| snake.0.heading());
+        let (piece, rotation) = if index == 0 {
+            (HEAD_SPRITE, facing(snake.0.heading()))
+        } else if index + 1 >= body.len() {
+            (TAIL_SPRITE, facing(ahead))
+        } else {
+            match towards(index, index + 1) {
+                Some(behind) => body_piece(ahead, behind),
+                None => (STRAIGHT_SPRITE, facing(ahead)),
+            }
+        };
+
+        sprite.index = piece;
+        transform.rotation = rotation;
     }
 }
 
+/// Pays out for the finished game, records a high score and shows the results over the arena.
+#[allow(clippy::too_many_arguments)]
 pub fn game_over(
     mut commands: Commands,
     mut reader: EventReader<SnakeGameOver>,
-    food: Query<Entity, With<Food>>,
-    segments: Query<Entity, With<SnakeSegment>>,
-    background: Query<Entity, With<Background>>,
+    mut shift_writer: EventWriter<ShiftFinished>,
+    snake: Res<Snake>,
+    mut high_scores: ResMut<HighScores>,
+    selected: Res<SelectedDifficulty>,
+    arena_kind: Res<ArenaKind>,
+    autopilot: Option<Res<Autopilot>>,
     mut app_state: ResMut<State<GameState>>,
     mut meowney: ResMut<Meowney>,
+    config: Res<SnakeConfig>,
+    move_timer: Res<MoveTimer>,
 ) {
-    if reader.iter().next().is_some() {
-        for ent in food.iter() {
-            commands.entity(ent).despawn();
-        }
-        let mut earned = 0;
-        for ent in segments.iter() {
-            commands.entity(ent).despawn();
-            earned += 1;
-        }
-        earned -= 2;
+    // Demo games don't pay out
+    if autopilot.is_some() {
+        return;
+    }
+
+    if let Some(SnakeGameOver(result)) = reader.iter().next() {
+        let tick_seconds = move_timer.0.duration().as_secs_f32();
+        let earned = config.earnings(snake.0.payout(*result), tick_seconds);
         meowney.0 += earned;
 
-        let background = background.single();
-        commands.entity(background).despawn();
+        let rank = if earned > 0 {
+            high_scores.insert(HighScore {
+                score: earned,
+                length: snake.0.length(),
+                difficulty: selected.0,
+                arena: arena_kind.clone(),
+                seed: snake.0.seed(),
+                date: high_scores::today(),
+            })
+        } else {
+            None
+        };
+        if rank.is_some() {
+            if let Err(error) = high_scores.save() {
+                warn!(?error, "failed to save high scores");
+            }
+        }
 
-        info!(meowney = %meowney.0, "meowney updated");
+        let results = SnakeResults::new(&snake.0, *result, earned, arena_kind.clone(), rank);
+        info!(?results, tick_seconds, meowney = %meowney.0, "meowney updated");
+        shift_writer.send(ShiftFinished(results.clone()));
+        commands.insert_resource(results);
 
-        app_state.set(GameState::Outside).unwrap();
+        app_state.push(GameState::PostOfficeResults).unwrap();
     }
 }
+
+#[instrument(skip(commands, food, segments, backgrounds))]
+pub fn teardown(
+    mut commands: Commands,
+    food: Query<Entity, With<Food>>,
+    segments: Query<Entity, With<SnakeSegment>>,
+    backgrounds: Query<Entity, With<Background>>,
+) {
+    info!("tearing down post office scene");
+    food.for_each(|entity| commands.entity(entity).despawn());
+    segments.for_each(|entity| commands.entity(entity).despawn());
+    backgrounds.for_each(|entity| commands.entity(entity).despawn());
+    commands.remove_resource::<Snake>();
+    commands.remove_resource::<ArenaKind>();
+    commands.remove_resource::<MoveTimer>();
+    commands.remove_resource::<SnakeAtlas>();
+    commands.remove_resource::<SnakeConfig>();
+    commands.remove_resource::<ArenaScale>();
+}
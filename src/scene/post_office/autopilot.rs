@@ -0,0 +1,341 @@
+//! A computer player for the snake minigame, for attract mode and soak tests.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+
+use anyhow::bail;
+use bevy::prelude::*;
+use rand::Rng;
+use tracing::instrument;
+
+use super::{
+    difficulty::{CustomDifficulty, Difficulty},
+    snake::{ArenaMode, Direction, Food, GameOver, Position, SnakeGame},
+    Snake, SnakeGameOver, SnakeHead,
+};
+use crate::{action::Action, rng::GameRng, GameState};
+
+/// Seconds without input on the main menu before the attract mode demo starts.
+pub const ATTRACT_IDLE_SECONDS: f32 = 30.;
+
+/// Steps a soak test game may last before it's counted as going round in circles.
+const MAX_SOAK_STEPS: u32 = 20_000;
+
+/// Present while the autopilot is driving the snake in attract mode.
+#[derive(Debug, Default)]
+pub struct Autopilot;
+
+/// Picks the next step towards the nearest food, or into the most room.
+pub fn choose_direction(game: &SnakeGame) -> Direction {
+    let arena = game.arena();
+    let blocked = game.body().chain(game.obstacles()).collect::<HashSet<_>>();
+    let food = game
+        .food()
+        .iter()
+        .map(|food| food.position)
+        .collect::<HashSet<_>>();
+
+    let first_steps = Direction::ALL
+        .into_iter()
+        .filter(|direction| *direction != game.heading().opposite())
+        .filter_map(|direction| {
+            arena
+                .neighbour(game.head(), direction)
+                .filter(|next| !blocked.contains(next))
+                .map(|next| (direction, next))
+        })
+        .collect::<Vec<_>>();
+
+    // Breadth-first search, remembering which first step reached each cell
+    let mut reached = HashMap::new();
+    let mut queue = VecDeque::new();
+    for (direction, next) in &first_steps {
+        reached.insert(*next, *direction);
+        queue.push_back(*next);
+    }
+    while let Some(cell) = queue.pop_front() {
+        let first_step = reached[&cell];
+        if food.contains(&cell) {
+            return first_step;
+        }
+
+        for direction in Direction::ALL {
+            if let Some(next) = arena.neighbour(cell, direction) {
+                if !blocked.contains(&next) && !reached.contains_key(&next) {
+                    reached.insert(next, first_step);
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    first_steps
+        .into_iter()
+        .max_by_key(|(_, next)| room_from(game, &blocked, *next))
+        .map_or(game.heading(), |(direction, _)| direction)
+}
+
+fn room_from(game: &SnakeGame, blocked: &HashSet<Position>, start: Position) -> usize {
+    let mut seen = HashSet::from([start]);
+    let mut queue = VecDeque::from([start]);
+    while let Some(cell) = queue.pop_front() {
+        for direction in Direction::ALL {
+            if let Some(next) = game.arena().neighbour(cell, direction) {
+                if !blocked.contains(&next) && seen.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+    }
+
+    seen.len()
+}
+
+/// Queues the autopilot's next turn.
+pub fn steer(
+    autopilot: Option<Res<Autopilot>>,
+    snake: Res<Snake>,
+    mut heads: Query<&mut SnakeHead>,
+) {
+    if autopilot.is_none() || !snake.is_changed() {
+        return;
+    }
+
+    let direction = choose_direction(&snake.0);
+    for mut head in heads.iter_mut() {
+        head.pending_turns.clear();
+        head.queue_turn(direction);
+    }
+}
+
+/// Starts the attract mode demo after the main menu has sat idle for a while.
+#[instrument(skip(commands, idle_seconds, time, keys, mouse_buttons, actions, app_state))]
+pub fn start_attract_mode(
+    mut commands: Commands,
+    mut idle_seconds: Local<f32>,
+    time: Res<Time>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    actions: Res<Input<Action>>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    let any_input = keys.get_pressed().next().is_some()
+        || mouse_buttons.get_pressed().next().is_some()
+        || actions.get_pressed().next().is_some();
+    if any_input {
+        *idle_seconds = 0.;
+        return;
+    }
+
+    *idle_seconds += time.delta_seconds();
+    if *idle_seconds >= ATTRACT_IDLE_SECONDS {
+        info!("starting attract mode");
+        *idle_seconds = 0.;
+        commands.insert_resource(Autopilot);
+        app_state.set(GameState::PostOffice).unwrap();
+    }
+}
+
+/// Ends the attract mode demo on any input, or when the game ends.
+#[instrument(skip(
+    commands,
+    autopilot,
+    keys,
+    mouse_buttons,
+    actions,
+    game_over_reader,
+    app_state
+))]
+pub fn stop_attract_mode(
+    mut commands: Commands,
+    autopilot: Option<Res<Autopilot>>,
+    keys: Res<Input<KeyCode>>,
+    mouse_buttons: Res<Input<MouseButton>>,
+    actions: Res<Input<Action>>,
+    mut game_over_reader: EventReader<SnakeGameOver>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    if autopilot.is_none() {
+        return;
+    }
+
+    let any_input = keys.get_just_pressed().next().is_some()
+        || mouse_buttons.get_just_pressed().next().is_some()
+        || actions.get_just_pressed().next().is_some();
+    let demo_over = game_over_reader.iter().next().is_some();
+    if !any_input && !demo_over {
+        return;
+    }
+
+    info!(any_input, demo_over, "stopping attract mode");
+    commands.remove_resource::<Autopilot>();
+    app_state.replace(GameState::MainMenu).unwrap();
+}
+
+/// How a batch of soak test games went.
+#[derive(Debug, Default)]
+pub struct SoakSummary {
+    pub runs: u32,
+    pub endings: BTreeMap<&'static str, u32>,
+    pub average_length: f32,
+    pub longest: usize,
+}
+
+/// Plays `runs` games of snake with the autopilot and no window, and logs how they went.
+#[instrument(skip(custom))]
+pub fn soak(runs: u32, seed: u64, custom: &CustomDifficulty) -> anyhow::Result<SoakSummary> {
+    let mut rng = GameRng::from_seed(seed);
+    let mut summary = SoakSummary {
+        runs,
+        ..Default::default()
+    };
+    let mut total_length = 0;
+
+    info!("soak testing games of snake");
+
+    for run in 0..runs {
+        let difficulty = Difficulty::ALL[run as usize % Difficulty::ALL.len()];
+        let mode = ArenaMode::ALL[run as usize / Difficulty::ALL.len() % ArenaMode::ALL.len()];
+        let config = difficulty.config(custom, mode);
+
+        let arena = config.arena();
+        let mut game = SnakeGame::new(
+            arena,
+            arena.clamp(Position { x: 3, y: 2 }),
+            Direction::Up,
+            2,
+            rng.gen(),
+        );
+        let mut clock = ExpiryClock::default();
+        // If there's no room for the first piece of food, the first step ends the game
+        if let Ok(food) = game.spawn_food() {
+            clock.placed(food);
+        }
+        let mut ending = None;
+        let mut steps = 0;
+
+        while ending.is_none() && steps < MAX_SOAK_STEPS {
+            let tick_seconds = config.tick_seconds_after(game.segments_added());
+            game.turn(choose_direction(&game));
+            let step = match game.step() {
+                Ok(step) => step,
+                Err(game_over) => {
+                    ending = Some(game_over);
+                    break;
+                }
+            };
+            steps += 1;
+
+            if let Some(food) = step.ate {
+                clock.eaten(food);
+            }
+            if let Some(food) = step.spawned {
+                clock.placed(food);
+            }
+            clock.advance(tick_seconds, &mut game);
+
+            check(&game).map_err(|error| {
+                error.context(format!(
+                    "run {run} ({difficulty:?}, {mode:?}) broke the rules after {steps} steps"
+                ))
+            })?;
+        }
+
+        let ending = match ending {
+            Some(GameOver::HitWall) => "hit wall",
+            Some(GameOver::HitOwnTail) => "hit own tail",
+            Some(GameOver::HitObstacle) => "hit obstacle",
+            Some(GameOver::BoardCleared) => "board cleared",
+            None => "ran out of steps",
+        };
+        *summary.endings.entry(ending).or_default() += 1;
+        total_length += game.length();
+        summary.longest = summary.longest.max(game.length());
+    }
+
+    summary.average_length = total_length as f32 / runs.max(1) as f32;
+    for (ending, count) in &summary.endings {
+        info!(ending, count, "soak test endings");
+    }
+    info!(
+        average_length = summary.average_length,
+        longest = summary.longest,
+        "soak test finished"
+    );
+
+    Ok(summary)
+}
+
+/// Stands in for the express mail timers, with time passing as fast as the snake moves.
+#[derive(Default)]
+struct ExpiryClock {
+    seconds: f32,
+    deadlines: Vec<(Position, f32)>,
+}
+
+impl ExpiryClock {
+    fn placed(&mut self, food: Food) {
+        if let Some(lifetime) = food.kind.lifetime_seconds() {
+            self.deadlines
+                .push((food.position, self.seconds + lifetime));
+        }
+    }
+
+    fn eaten(&mut self, food: Food) {
+        self.deadlines
+            .retain(|(position, _)| *position != food.position);
+    }
+
+    fn advance(&mut self, seconds: f32, game: &mut SnakeGame) {
+        self.seconds += seconds;
+        let now = self.seconds;
+        let (expired, waiting) = self
+            .deadlines
+            .drain(..)
+            .partition::<Vec<_>, _>(|(_, deadline)| *deadline <= now);
+        self.deadlines = waiting;
+
+        for (position, _) in expired {
+            if let Some(replacement) = game.expire_food(position) {
+                self.placed(replacement);
+            }
+        }
+    }
+}
+
+/// Checks the snake is in one piece, inside the arena and clear of obstacles and food.
+fn check(game: &SnakeGame) -> anyhow::Result<()> {
+    let obstacles = game.obstacles().collect::<HashSet<_>>();
+    let mut seen = HashSet::new();
+    for segment in game.body() {
+        if !game.arena().contains(segment) {
+            bail!("segment {segment:?} is outside the arena");
+        }
+        if obstacles.contains(&segment) {
+            bail!("segment {segment:?} is on an obstacle");
+        }
+        if game.has_food_at(segment) {
+            bail!("segment {segment:?} is on uneaten food");
+        }
+        if !seen.insert(segment) {
+            bail!("segment {segment:?} overlaps another segment");
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scene::post_office::difficulty::SnakeConfig;
+
+    #[test]
+    fn soak_test_plays_every_run_by_the_rules() {
+        let runs = (Difficulty::ALL.len() * ArenaMode::ALL.len()) as u32;
+        let summary = soak(runs, 1, &CustomDifficulty(SnakeConfig::default())).unwrap();
+
+        assert_eq!(summary.runs, runs);
+        assert_eq!(summary.endings.values().sum::<u32>(), runs);
+        assert!(summary.longest >= 2);
+    }
+}
@@ -0,0 +1,184 @@
+//! Difficulty presets for the snake minigame.
+
+use serde::{Deserialize, Serialize};
+
+use super::snake::{Arena, ArenaMode};
+use crate::config;
+
+const CUSTOM_DIFFICULTY_FILE_NAME: &str = "post_office.ron";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+    /// Settings read from `post_office.ron` in the config directory.
+    Custom,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::Custom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Custom => "Custom",
+        }
+    }
+
+    /// Settings for this difficulty. Presets play in `mode`, while custom settings bring their
+    /// own arena mode.
+    pub fn config(self, custom: &CustomDifficulty, mode: ArenaMode) -> SnakeConfig {
+        match self {
+            Difficulty::Easy => SnakeConfig {
+                half_width: 7,
+                half_height: 7,
+                tick_seconds: 0.3,
+                speed_up: 0.97,
+                min_tick_seconds: 0.15,
+                payout_multiplier: 0.5,
+                mode,
+                ..Default::default()
+            },
+            Difficulty::Normal => SnakeConfig {
+                mode,
+                ..Default::default()
+            },
+            Difficulty::Hard => SnakeConfig {
+                half_width: 4,
+                half_height: 4,
+                tick_seconds: 0.12,
+                speed_up: 0.93,
+                min_tick_seconds: 0.06,
+                payout_multiplier: 2.,
+                mode,
+                ..Default::default()
+            },
+            Difficulty::Custom => custom.0.clone().sanitized(),
+        }
+    }
+}
+
+/// Settings for a game of snake.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SnakeConfig {
+    /// The arena spans `-half_width..=half_width` cells horizontally.
+    pub half_width: i32,
+    /// The arena spans `-half_height..=half_height` cells vertically.
+    pub half_height: i32,
+    /// Seconds between each step of the snake when the run starts.
+    pub tick_seconds: f32,
+    /// Factor the time between steps is multiplied by for each segment the snake grows.
+    pub speed_up: f32,
+    /// The shortest time between steps the snake can speed up to.
+    pub min_tick_seconds: f32,
+    /// Multiplier applied to the Meowney earned in a run.
+    pub payout_multiplier: f32,
+    /// What happens at the edge of the arena. The presets use the mode chosen in the prompt.
+    pub mode: ArenaMode,
+}
+
+impl Default for SnakeConfig {
+    fn default() -> Self {
+        Self {
+            half_width: 5,
+            half_height: 5,
+            tick_seconds: 0.2,
+            speed_up: 0.95,
+            min_tick_seconds: 0.1,
+            payout_multiplier: 1.,
+            mode: ArenaMode::default(),
+        }
+    }
+}
+
+impl SnakeConfig {
+    /// Clamps hand-edited settings to values the minigame can run with.
+    fn sanitized(self) -> Self {
+        Self {
+            half_width: self.half_width.clamp(1, 20),
+            half_height: self.half_height.clamp(1, 20),
+            tick_seconds: self.tick_seconds.max(0.02),
+            speed_up: self.speed_up.clamp(0.5, 1.),
+            min_tick_seconds: self
+                .min_tick_seconds
+                .clamp(0.02, self.tick_seconds.max(0.02)),
+            payout_multiplier: self.payout_multiplier.max(0.),
+            mode: self.mode,
+        }
+    }
+
+    pub fn arena(&self) -> Arena {
+        Arena {
+            half_width: self.half_width,
+            half_height: self.half_height,
+            mode: self.mode,
+        }
+    }
+
+    /// Seconds between each step once the snake has grown by `segments_added`.
+    pub fn tick_seconds_after(&self, segments_added: usize) -> f32 {
+        let tick = self.tick_seconds * self.speed_up.powi(segments_added as i32);
+        tick.max(self.min_tick_seconds.min(self.tick_seconds))
+    }
+
+    /// Meowney earned for a run paying `payout`, which had sped up to `tick_seconds` per step.
+    ///
+    /// Runs that got faster pay proportionally more.
+    pub fn earnings(&self, payout: u32, tick_seconds: f32) -> u32 {
+        let speed_bonus = self.tick_seconds / tick_seconds;
+        (payout as f32 * self.payout_multiplier * speed_bonus).round() as u32
+    }
+
+    pub fn arena_width(&self) -> i32 {
+        self.half_width * 2 + 1
+    }
+
+    pub fn arena_height(&self) -> i32 {
+        self.half_height * 2 + 1
+    }
+
+    /// A one line summary for the difficulty prompt.
+    pub fn describe(&self) -> String {
+        format!(
+            "{}x{} arena, {:.2}s to {:.2}s per step, {}x pay",
+            self.arena_width(),
+            self.arena_height(),
+            self.tick_seconds,
+            self.min_tick_seconds,
+            self.payout_multiplier
+        )
+    }
+}
+
+/// The difficulty chosen for the next game of snake.
+#[derive(Debug)]
+pub struct SelectedDifficulty(pub Difficulty);
+
+impl Default for SelectedDifficulty {
+    fn default() -> Self {
+        Self(Difficulty::Normal)
+    }
+}
+
+/// The arena mode chosen for the next game of snake.
+#[derive(Debug, Default)]
+pub struct SelectedArenaMode(pub ArenaMode);
+
+/// Settings for [`Difficulty::Custom`].
+#[derive(Debug)]
+pub struct CustomDifficulty(pub SnakeConfig);
+
+impl CustomDifficulty {
+    pub fn load() -> Self {
+        Self(config::load(CUSTOM_DIFFICULTY_FILE_NAME))
+    }
+}
@@ -0,0 +1,118 @@
+//! Obstacle layouts for the snake arena, loaded from `.layout` files in `assets/layouts`.
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    utils::BoxedFuture,
+};
+use bevy_asset_loader::AssetCollection;
+use serde::Deserialize;
+
+use super::snake::{Direction, Position};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+pub enum ObstacleKind {
+    MailCrate,
+    SortingTable,
+    Counter,
+}
+
+impl ObstacleKind {
+    /// Index of this obstacle's sprite in `images/obstacles.png`.
+    pub fn sprite_index(self) -> usize {
+        match self {
+            ObstacleKind::MailCrate => 0,
+            ObstacleKind::SortingTable => 1,
+            ObstacleKind::Counter => 2,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Obstacle {
+    pub kind: ObstacleKind,
+    pub cells: Vec<Position>,
+}
+
+/// A layout of obstacles in the snake arena, and where the snake starts.
+///
+/// Cells that fall outside the chosen arena are left out, so a layout can be shared between
+/// arena sizes.
+#[derive(Clone, Debug, Deserialize, TypeUuid)]
+#[uuid = "bae8d36b-4974-4d75-94f1-57cfdd5348b9"]
+pub struct ArenaLayout {
+    pub name: String,
+    #[serde(default = "default_start")]
+    pub start: Position,
+    #[serde(default = "default_heading")]
+    pub heading: Direction,
+    #[serde(default)]
+    pub obstacles: Vec<Obstacle>,
+}
+
+fn default_start() -> Position {
+    Position { x: 3, y: 2 }
+}
+
+fn default_heading() -> Direction {
+    Direction::Up
+}
+
+impl ArenaLayout {
+    /// Every obstacle cell along with the kind of obstacle in it.
+    pub fn obstacle_cells(&self) -> impl Iterator<Item = (Position, ObstacleKind)> + '_ {
+        self.obstacles
+            .iter()
+            .flat_map(|obstacle| obstacle.cells.iter().map(|cell| (*cell, obstacle.kind)))
+    }
+}
+
+#[derive(Default)]
+pub struct ArenaLayoutLoader;
+
+impl AssetLoader for ArenaLayoutLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let layout: ArenaLayout = ron::de::from_bytes(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(layout));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["layout"]
+    }
+}
+
+#[derive(AssetCollection)]
+pub struct Layouts {
+    #[asset(path = "layouts/empty.layout")]
+    pub empty: Handle<ArenaLayout>,
+    #[asset(path = "layouts/mail_crates.layout")]
+    pub mail_crates: Handle<ArenaLayout>,
+    #[asset(path = "layouts/sorting_tables.layout")]
+    pub sorting_tables: Handle<ArenaLayout>,
+    #[asset(path = "layouts/counters.layout")]
+    pub counters: Handle<ArenaLayout>,
+}
+
+impl Layouts {
+    /// Every layout, in the order they are offered in the difficulty prompt.
+    pub fn all(&self) -> [&Handle<ArenaLayout>; 4] {
+        [
+            &self.empty,
+            &self.mail_crates,
+            &self.sorting_tables,
+            &self.counters,
+        ]
+    }
+}
+
+/// Index into [`Layouts::all`] of the layout chosen for the next game of snake.
+#[derive(Debug, Default)]
+pub struct SelectedLayout(pub usize);
@@ -0,0 +1,242 @@
+//! Prompt for choosing a difficulty before starting the snake minigame.
+
+use bevy::prelude::*;
+use tracing::instrument;
+
+use super::{
+    difficulty::{CustomDifficulty, Difficulty, SelectedArenaMode, SelectedDifficulty},
+    layout::{ArenaLayout, Layouts, SelectedLayout},
+    snake::ArenaMode,
+};
+use crate::{
+    assets::Fonts,
+    focus::{Activated, Focusable},
+    ui::{BUTTON_COLOR, PANEL_COLOR},
+    GameState,
+};
+
+const SELECTED_BUTTON_COLOR: Color = Color::rgb(0.8, 0.55, 0.3);
+
+#[derive(Component)]
+pub struct DifficultyPrompt;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PromptChoice {
+    Mode(ArenaMode),
+    /// Index into [`Layouts::all`].
+    Layout(usize),
+    Difficulty(Difficulty),
+    BackToTown,
+}
+
+#[instrument(skip(commands, fonts, custom, layouts, layout_assets))]
+pub fn setup(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    custom: Res<CustomDifficulty>,
+    layouts: Res<Layouts>,
+    layout_assets: Res<Assets<ArenaLayout>>,
+) {
+    info!("setting up post office difficulty prompt");
+
+    let rows = [
+        ArenaMode::ALL
+            .into_iter()
+            .map(|mode| (PromptChoice::Mode(mode), mode.name().to_string()))
+            .collect::<Vec<_>>(),
+        layouts
+            .all()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(index, handle)| {
+                let layout = layout_assets.get(handle)?;
+                Some((PromptChoice::Layout(index), layout.name.clone()))
+            })
+            .collect(),
+    ];
+
+    let text_style = |font_size| TextStyle {
+        font: fonts.vt323.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: PANEL_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(Name::new("Difficulty Prompt"))
+        .insert(DifficultyPrompt)
+        .with_children(|parent| {
+            // Buttons are focused in the order they're laid out, row by row
+            let mut next_focusable = (0..).map(Focusable);
+
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(24.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(
+                    "How busy a shift do you want at the post office?",
+                    text_style(48.),
+                    Default::default(),
+                ),
+                ..Default::default()
+            });
+
+            for row in rows {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            margin: Rect::all(Val::Px(8.)),
+                            ..Default::default()
+                        },
+                        color: Color::NONE.into(),
+                        ..Default::default()
+                    })
+                    .with_children(|parent| {
+                        for (choice, label) in row {
+                            parent
+                                .spawn_bundle(ButtonBundle {
+                                    style: Style {
+                                        margin: Rect::all(Val::Px(8.)),
+                                        padding: Rect::all(Val::Px(8.)),
+                                        ..Default::default()
+                                    },
+                                    color: BUTTON_COLOR.into(),
+                                    ..Default::default()
+                                })
+                                .insert(choice)
+                                .insert(next_focusable.next().unwrap())
+                                .with_children(|parent| {
+                                    parent.spawn_bundle(TextBundle {
+                                        text: Text::with_section(
+                                            label,
+                                            text_style(36.),
+                                            Default::default(),
+                                        ),
+                                        ..Default::default()
+                                    });
+                                });
+                        }
+                    });
+            }
+
+            let choices = Difficulty::ALL
+                .into_iter()
+                .map(|difficulty| {
+                    let config = difficulty.config(&custom, ArenaMode::default());
+                    // Custom settings ignore the mode buttons, so say which mode they play in
+                    let name = match difficulty {
+                        Difficulty::Custom => {
+                            format!("{} ({})", difficulty.name(), config.mode.name())
+                        }
+                        _ => difficulty.name().to_string(),
+                    };
+                    (
+                        PromptChoice::Difficulty(difficulty),
+                        format!("{name} - {}", config.describe()),
+                    )
+                })
+                .chain([(PromptChoice::BackToTown, "Back to town".to_string())]);
+
+            for (choice, label) in choices {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(8.)),
+                            padding: Rect::all(Val::Px(8.)),
+                            ..Default::default()
+                        },
+                        color: BUTTON_COLOR.into(),
+                        ..Default::default()
+                    })
+                    .insert(choice)
+                    .insert(next_focusable.next().unwrap())
+                    .with_children(|parent| {
+                        parent.spawn_bundle(TextBundle {
+                            text: Text::with_section(label, text_style(36.), Default::default()),
+                            ..Default::default()
+                        });
+                    });
+            }
+        });
+}
+
+#[instrument(skip(activated, query, selected, selected_mode, selected_layout, app_state))]
+pub fn choose(
+    mut activated: EventReader<Activated>,
+    query: Query<&PromptChoice>,
+    mut selected: ResMut<SelectedDifficulty>,
+    mut selected_mode: ResMut<SelectedArenaMode>,
+    mut selected_layout: ResMut<SelectedLayout>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    for choice in activated
+        .iter()
+        .filter_map(|Activated(entity)| query.get(*entity).ok())
+    {
+        match choice {
+            PromptChoice::Mode(mode) => {
+                info!(?mode, "arena mode chosen");
+                selected_mode.0 = *mode;
+            }
+            PromptChoice::Layout(index) => {
+                info!(index, "arena layout chosen");
+                selected_layout.0 = *index;
+            }
+            PromptChoice::Difficulty(difficulty) => {
+                info!(?difficulty, "difficulty chosen");
+                selected.0 = *difficulty;
+                app_state.set(GameState::PostOffice).unwrap();
+            }
+            PromptChoice::BackToTown => {
+                info!("leaving post office");
+                app_state.set(GameState::Outside).unwrap();
+            }
+        }
+
+        return;
+    }
+}
+
+/// Highlights the selected arena mode and layout.
+pub fn highlight_selected(
+    selected_mode: Res<SelectedArenaMode>,
+    selected_layout: Res<SelectedLayout>,
+    mut query: Query<(&PromptChoice, &mut UiColor)>,
+) {
+    for (choice, mut color) in query.iter_mut() {
+        let selected = match choice {
+            PromptChoice::Mode(mode) => *mode == selected_mode.0,
+            PromptChoice::Layout(index) => *index == selected_layout.0,
+            _ => continue,
+        };
+        let new_color = if selected {
+            SELECTED_BUTTON_COLOR
+        } else {
+            BUTTON_COLOR
+        };
+        if color.0 != new_color {
+            color.0 = new_color;
+        }
+    }
+}
+
+#[instrument(skip(commands, query))]
+pub fn teardown(mut commands: Commands, query: Query<Entity, With<DifficultyPrompt>>) {
+    info!("tearing down post office difficulty prompt");
+    query.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
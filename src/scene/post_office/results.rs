@@ -0,0 +1,267 @@
+//! Results panel shown over the arena at the end of a game of snake.
+
+use bevy::prelude::*;
+use tracing::instrument;
+
+use super::snake::{FoodKind, GameOver, SnakeGame};
+use crate::{
+    assets::Fonts,
+    focus::{Activated, Focusable},
+    high_scores::{ArenaKind, HighScores},
+    scene::high_scores::spawn_table,
+    ui::{BUTTON_COLOR, HIGHLIGHT_COLOR, OVERLAY_PANEL_COLOR},
+    GameState,
+};
+
+/// Number of high scores listed under the results.
+const HIGH_SCORES_SHOWN: usize = 5;
+
+/// How the last game of snake went.
+#[derive(Debug, Clone)]
+pub struct SnakeResults {
+    pub game_over: GameOver,
+    pub letters: u32,
+    pub parcels: u32,
+    pub express: u32,
+    pub earned: u32,
+    /// The kind of arena the game was played in.
+    pub arena: ArenaKind,
+    /// Place in the high score table for the arena, counting from 0, if the run made it in.
+    pub rank: Option<usize>,
+}
+
+impl SnakeResults {
+    pub fn new(
+        snake: &SnakeGame,
+        game_over: GameOver,
+        earned: u32,
+        arena: ArenaKind,
+        rank: Option<usize>,
+    ) -> Self {
+        Self {
+            game_over,
+            letters: snake.delivered_of(FoodKind::Letter),
+            parcels: snake.delivered_of(FoodKind::Parcel),
+            express: snake.delivered_of(FoodKind::Express),
+            earned,
+            arena,
+            rank,
+        }
+    }
+
+    pub fn is_personal_best(&self) -> bool {
+        self.rank == Some(0)
+    }
+}
+
+#[derive(Component)]
+pub struct ResultsPanel;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResultsChoice {
+    TryAgain,
+    BackToTown,
+}
+
+fn cause_text(game_over: GameOver) -> &'static str {
+    match game_over {
+        GameOver::HitWall => "You ran into the side of the sorting room.",
+        GameOver::HitOwnTail => "You tripped over your own tail.",
+        GameOver::HitObstacle => "You crashed into the furniture.",
+        GameOver::BoardCleared => "You filled the whole room. Bonus!",
+    }
+}
+
+#[instrument(skip(commands, fonts, results, high_scores))]
+pub fn setup(
+    mut commands: Commands,
+    fonts: Res<Fonts>,
+    results: Res<SnakeResults>,
+    high_scores: Res<HighScores>,
+) {
+    info!("setting up snake results");
+
+    let text_style = |font_size| TextStyle {
+        font: fonts.vt323.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+    let title = if results.game_over.is_win() {
+        "Board cleared!"
+    } else {
+        "Shift over!"
+    };
+    let lines = [
+        cause_text(results.game_over).to_string(),
+        format!("Letters delivered: {}", results.letters),
+        format!("Parcels delivered: {}", results.parcels),
+        format!("Express mail delivered: {}", results.express),
+        format!("Meowney earned: {}", results.earned),
+    ];
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: OVERLAY_PANEL_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(Name::new("Snake Results"))
+        .insert(ResultsPanel)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(24.)),
+                    ..Default::default()
+                },
+                text: Text::with_section(title, text_style(64.), Default::default()),
+                ..Default::default()
+            });
+
+            if results.is_personal_best() {
+                parent.spawn_bundle(TextBundle {
+                    text: Text::with_section(
+                        "New personal best!",
+                        TextStyle {
+                            color: HIGHLIGHT_COLOR,
+                            ..text_style(48.)
+                        },
+                        Default::default(),
+                    ),
+                    ..Default::default()
+                });
+            }
+
+            for line in lines {
+                parent.spawn_bundle(TextBundle {
+                    style: Style {
+                        margin: Rect::all(Val::Px(4.)),
+                        ..Default::default()
+                    },
+                    text: Text::with_section(line, text_style(36.), Default::default()),
+                    ..Default::default()
+                });
+            }
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::ColumnReverse,
+                        align_items: AlignItems::Center,
+                        margin: Rect {
+                            top: Val::Px(24.),
+                            ..Default::default()
+                        },
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    parent.spawn_bundle(TextBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(4.)),
+                            ..Default::default()
+                        },
+                        text: Text::with_section(
+                            format!("Best shifts: {}", results.arena.name()),
+                            text_style(32.),
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    });
+                    spawn_table(
+                        parent,
+                        high_scores.for_arena(&results.arena),
+                        HIGH_SCORES_SHOWN,
+                        results.rank,
+                        text_style(28.),
+                    );
+                });
+
+            parent
+                .spawn_bundle(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Row,
+                        margin: Rect::all(Val::Px(24.)),
+                        ..Default::default()
+                    },
+                    color: Color::NONE.into(),
+                    ..Default::default()
+                })
+                .with_children(|parent| {
+                    for (index, (choice, label)) in [
+                        (ResultsChoice::TryAgain, "Try again"),
+                        (ResultsChoice::BackToTown, "Back to town"),
+                    ]
+                    .into_iter()
+                    .enumerate()
+                    {
+                        parent
+                            .spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    margin: Rect::all(Val::Px(8.)),
+                                    padding: Rect::all(Val::Px(8.)),
+                                    ..Default::default()
+                                },
+                                color: BUTTON_COLOR.into(),
+                                ..Default::default()
+                            })
+                            .insert(choice)
+                            .insert(Focusable(index))
+                            .with_children(|parent| {
+                                parent.spawn_bundle(TextBundle {
+                                    text: Text::with_section(
+                                        label,
+                                        text_style(36.),
+                                        Default::default(),
+                                    ),
+                                    ..Default::default()
+                                });
+                            });
+                    }
+                });
+        });
+}
+
+#[instrument(skip(activated, query, app_state))]
+pub fn choose(
+    mut activated: EventReader<Activated>,
+    query: Query<&ResultsChoice>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    for choice in activated
+        .iter()
+        .filter_map(|Activated(entity)| query.get(*entity).ok())
+    {
+        // Replacing the whole stack also tears down the arena underneath the results
+        match choice {
+            ResultsChoice::TryAgain => {
+                info!("trying snake again");
+                app_state.replace(GameState::PostOffice).unwrap();
+            }
+            ResultsChoice::BackToTown => {
+                info!("leaving post office");
+                app_state.replace(GameState::Outside).unwrap();
+            }
+        }
+
+        return;
+    }
+}
+
+#[instrument(skip(commands, query))]
+pub fn teardown(mut commands: Commands, query: Query<Entity, With<ResultsPanel>>) {
+    info!("tearing down snake results");
+    query.for_each(|entity| commands.entity(entity).despawn_recursive());
+    commands.remove_resource::<SnakeResults>();
+}
@@ -0,0 +1,770 @@
+//! Rules of the snake minigame, kept free of the ECS so they can be driven headlessly.
+
+use std::collections::{HashSet, VecDeque};
+
+use rand::{rngs::StdRng, seq::IteratorRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Position {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Position {
+    pub fn step(self, direction: Direction) -> Self {
+        match direction {
+            Direction::Left => Self {
+                x: self.x - 1,
+                ..self
+            },
+            Direction::Down => Self {
+                y: self.y - 1,
+                ..self
+            },
+            Direction::Up => Self {
+                y: self.y + 1,
+                ..self
+            },
+            Direction::Right => Self {
+                x: self.x + 1,
+                ..self
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Direction {
+    Left,
+    Down,
+    Up,
+    Right,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 4] = [
+        Direction::Up,
+        Direction::Left,
+        Direction::Down,
+        Direction::Right,
+    ];
+
+    pub fn opposite(self) -> Self {
+        match self {
+            Direction::Left => Self::Right,
+            Direction::Down => Self::Up,
+            Direction::Up => Self::Down,
+            Direction::Right => Self::Left,
+        }
+    }
+}
+
+/// What happens when the snake reaches the edge of the arena.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ArenaMode {
+    /// Leaving the arena ends the game.
+    #[default]
+    Walled,
+    /// Leaving the arena brings the snake back in on the opposite side.
+    Wrap,
+    /// Like [`ArenaMode::Walled`], but with a visible fence around the arena.
+    Fenced,
+}
+
+impl ArenaMode {
+    pub const ALL: [ArenaMode; 3] = [ArenaMode::Walled, ArenaMode::Wrap, ArenaMode::Fenced];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ArenaMode::Walled => "Walled",
+            ArenaMode::Wrap => "Wrap-around",
+            ArenaMode::Fenced => "Fenced",
+        }
+    }
+
+    pub fn shows_border(self) -> bool {
+        self == ArenaMode::Fenced
+    }
+}
+
+/// The grid the snake moves on, centred on (0, 0).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Arena {
+    pub half_width: i32,
+    pub half_height: i32,
+    pub mode: ArenaMode,
+}
+
+impl Arena {
+    pub fn contains(&self, position: Position) -> bool {
+        (-self.half_width..=self.half_width).contains(&position.x)
+            && (-self.half_height..=self.half_height).contains(&position.y)
+    }
+
+    /// Moves a position outside the arena to the nearest cell inside it.
+    pub fn clamp(&self, position: Position) -> Position {
+        Position {
+            x: position.x.clamp(-self.half_width, self.half_width),
+            y: position.y.clamp(-self.half_height, self.half_height),
+        }
+    }
+
+    /// Brings a position outside the arena back in on the opposite side.
+    pub fn wrap(&self, position: Position) -> Position {
+        let wrap = |value: i32, half: i32| (value + half).rem_euclid(half * 2 + 1) - half;
+        Position {
+            x: wrap(position.x, self.half_width),
+            y: wrap(position.y, self.half_height),
+        }
+    }
+
+    /// The cell next to `position` in `direction`, or `None` if that would leave the arena.
+    pub fn neighbour(&self, position: Position, direction: Direction) -> Option<Position> {
+        let next = position.step(direction);
+        if self.contains(next) {
+            Some(next)
+        } else if self.mode == ArenaMode::Wrap {
+            Some(self.wrap(next))
+        } else {
+            None
+        }
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = Position> + '_ {
+        (-self.half_height..=self.half_height)
+            .flat_map(move |y| (-self.half_width..=self.half_width).map(move |x| Position { x, y }))
+    }
+
+    /// The ring of cells just outside the arena.
+    pub fn border(&self) -> impl Iterator<Item = Position> + '_ {
+        let (width, height) = (self.half_width + 1, self.half_height + 1);
+        (-height..=height).flat_map(move |y| {
+            (-width..=width)
+                .filter(move |x| y.abs() == height || x.abs() == width)
+                .map(move |x| Position { x, y })
+        })
+    }
+}
+
+/// The kinds of mail the snake can pick up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FoodKind {
+    Letter,
+    /// Heavy, so the snake grows by two segments when it picks one up.
+    Parcel,
+    /// Pays extra, but disappears if it isn't picked up in time.
+    Express,
+}
+
+impl FoodKind {
+    pub const ALL: [FoodKind; 3] = [FoodKind::Letter, FoodKind::Parcel, FoodKind::Express];
+
+    pub fn random(rng: &mut impl Rng) -> Self {
+        match rng.gen_range(0..10) {
+            0..=6 => FoodKind::Letter,
+            7 | 8 => FoodKind::Parcel,
+            _ => FoodKind::Express,
+        }
+    }
+
+    pub fn segments(self) -> usize {
+        match self {
+            FoodKind::Letter | FoodKind::Express => 1,
+            FoodKind::Parcel => 2,
+        }
+    }
+
+    pub fn payout(self) -> u32 {
+        match self {
+            FoodKind::Letter => 1,
+            FoodKind::Parcel => 2,
+            FoodKind::Express => 3,
+        }
+    }
+
+    /// Seconds this stays in the arena before disappearing, if it ever does.
+    pub fn lifetime_seconds(self) -> Option<f32> {
+        match self {
+            FoodKind::Express => Some(5.),
+            FoodKind::Letter | FoodKind::Parcel => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Food {
+    pub position: Position,
+    pub kind: FoodKind,
+}
+
+/// Meowney paid on top of deliveries for filling the whole arena.
+pub const BOARD_CLEARED_BONUS: u32 = 10;
+
+/// Why a game of snake ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameOver {
+    HitWall,
+    HitOwnTail,
+    HitObstacle,
+    /// The snake filled the arena so there was nowhere left to put food.
+    BoardCleared,
+}
+
+impl GameOver {
+    pub fn is_win(self) -> bool {
+        self == GameOver::BoardCleared
+    }
+}
+
+/// What happened during a successful [`SnakeGame::step`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub head: Position,
+    pub ate: Option<Food>,
+    pub grew: bool,
+    pub spawned: Option<Food>,
+}
+
+/// Something besides stepping that changed the course of a game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GameEvent {
+    Turn(Direction),
+    Expire(Position),
+}
+
+#[derive(Debug, Clone)]
+pub struct SnakeGame {
+    arena: Arena,
+    obstacles: HashSet<Position>,
+    body: VecDeque<Position>,
+    initial_length: usize,
+    heading: Direction,
+    food: Vec<Food>,
+    pending_growth: usize,
+    delivered: Vec<FoodKind>,
+    seed: u64,
+    rng: StdRng,
+    steps: u64,
+    history: Vec<(u64, GameEvent)>,
+}
+
+impl SnakeGame {
+    /// Creates a game with every segment of the snake stacked on `start`.
+    pub fn new(
+        arena: Arena,
+        start: Position,
+        heading: Direction,
+        length: usize,
+        seed: u64,
+    ) -> Self {
+        let length = length.max(1);
+        Self {
+            arena,
+            obstacles: HashSet::new(),
+            body: std::iter::repeat(start).take(length).collect(),
+            initial_length: length,
+            heading,
+            food: Vec::new(),
+            pending_growth: 0,
+            delivered: Vec::new(),
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+            steps: 0,
+            history: Vec::new(),
+        }
+    }
+
+    /// Adds obstacles to the arena, leaving out any cells outside it.
+    pub fn with_obstacles(mut self, cells: impl IntoIterator<Item = Position>) -> Self {
+        let arena = self.arena;
+        self.obstacles
+            .extend(cells.into_iter().filter(|cell| arena.contains(*cell)));
+        self
+    }
+
+    pub fn obstacles(&self) -> impl Iterator<Item = Position> + '_ {
+        self.obstacles.iter().copied()
+    }
+
+    pub fn head(&self) -> Position {
+        self.body[0]
+    }
+
+    pub fn tail(&self) -> Position {
+        *self.body.back().unwrap()
+    }
+
+    /// Segment positions, head first.
+    pub fn body(&self) -> impl Iterator<Item = Position> + '_ {
+        self.body.iter().copied()
+    }
+
+    pub fn length(&self) -> usize {
+        self.body.len()
+    }
+
+    /// Number of segments the snake has grown by since the start of the game.
+    pub fn segments_added(&self) -> usize {
+        self.body.len() - self.initial_length
+    }
+
+    pub fn heading(&self) -> Direction {
+        self.heading
+    }
+
+    pub fn food(&self) -> &[Food] {
+        &self.food
+    }
+
+    pub fn has_food_at(&self, position: Position) -> bool {
+        self.food.iter().any(|food| food.position == position)
+    }
+
+    pub fn delivered(&self) -> u32 {
+        self.delivered.len() as u32
+    }
+
+    /// Number of pieces of food of one kind eaten so far.
+    pub fn delivered_of(&self, kind: FoodKind) -> u32 {
+        self.delivered
+            .iter()
+            .filter(|eaten| **eaten == kind)
+            .count() as u32
+    }
+
+    /// Meowney earned for a game that ended with `game_over`.
+    pub fn payout(&self, game_over: GameOver) -> u32 {
+        let deliveries = self.delivered.iter().map(|kind| kind.payout()).sum::<u32>();
+        match game_over {
+            GameOver::BoardCleared => deliveries + BOARD_CLEARED_BONUS,
+            GameOver::HitWall | GameOver::HitOwnTail | GameOver::HitObstacle => deliveries,
+        }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn arena(&self) -> &Arena {
+        &self.arena
+    }
+
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Turns and expired food so far, with the number of steps taken when each happened.
+    pub fn history(&self) -> &[(u64, GameEvent)] {
+        &self.history
+    }
+
+    /// Applies a turn or expired food from another game's [`history`](Self::history).
+    pub fn apply(&mut self, event: GameEvent) -> Option<Food> {
+        match event {
+            GameEvent::Turn(direction) => {
+                self.turn(direction);
+                None
+            }
+            GameEvent::Expire(position) => self.expire_food(position),
+        }
+    }
+
+    /// Whether a cell is inside the arena and clear of obstacles, the snake and food.
+    pub fn is_free(&self, position: Position) -> bool {
+        self.arena.contains(position)
+            && !self.obstacles.contains(&position)
+            && !self.body.contains(&position)
+            && !self.has_food_at(position)
+    }
+
+    pub fn free_cells(&self) -> impl Iterator<Item = Position> + '_ {
+        self.arena
+            .cells()
+            .filter(move |position| self.is_free(*position))
+    }
+
+    /// Sets the direction of the next step, unless it would reverse the snake into itself.
+    pub fn turn(&mut self, direction: Direction) -> bool {
+        if direction == self.heading.opposite() {
+            return false;
+        }
+
+        if direction != self.heading {
+            self.history.push((self.steps, GameEvent::Turn(direction)));
+        }
+        self.heading = direction;
+        true
+    }
+
+    pub fn place_food(&mut self, position: Position, kind: FoodKind) -> Food {
+        let food = Food { position, kind };
+        self.food.push(food);
+        food
+    }
+
+    /// Places a random kind of food on a random free cell.
+    pub fn spawn_food(&mut self) -> Result<Food, GameOver> {
+        // Collected first, as choosing borrows the game's randomness
+        let free_cells = self.free_cells().collect::<Vec<_>>();
+        let position = free_cells
+            .into_iter()
+            .choose(&mut self.rng)
+            .ok_or(GameOver::BoardCleared)?;
+
+        let kind = FoodKind::random(&mut self.rng);
+        Ok(self.place_food(position, kind))
+    }
+
+    /// Moves the snake one cell along its heading, eating any food it lands on.
+    pub fn step(&mut self) -> Result<Step, GameOver> {
+        if self.food.is_empty() && self.free_cells().next().is_none() {
+            return Err(GameOver::BoardCleared);
+        }
+
+        let head = self
+            .arena
+            .neighbour(self.head(), self.heading)
+            .ok_or(GameOver::HitWall)?;
+        if self.obstacles.contains(&head) {
+            return Err(GameOver::HitObstacle);
+        }
+        // The tail moves out of the way on this step, unless the snake is still growing
+        let blocking = if self.pending_growth > 0 {
+            self.body.len()
+        } else {
+            self.body.len() - 1
+        };
+        if self
+            .body
+            .iter()
+            .take(blocking)
+            .any(|segment| *segment == head)
+        {
+            return Err(GameOver::HitOwnTail);
+        }
+
+        let ate = self
+            .food
+            .iter()
+            .position(|food| food.position == head)
+            .map(|index| self.food.swap_remove(index));
+        if let Some(food) = ate {
+            self.delivered.push(food.kind);
+            self.pending_growth += food.kind.segments();
+        }
+
+        self.body.push_front(head);
+        let grew = self.pending_growth > 0;
+        if grew {
+            self.pending_growth -= 1;
+        } else {
+            self.body.pop_back();
+        }
+
+        let spawned = ate.and_then(|_| self.spawn_food().ok());
+        self.steps += 1;
+
+        Ok(Step {
+            head,
+            ate,
+            grew,
+            spawned,
+        })
+    }
+
+    /// Takes away the food at `position` and returns the food placed to replace it.
+    pub fn expire_food(&mut self, position: Position) -> Option<Food> {
+        let index = self
+            .food
+            .iter()
+            .position(|food| food.position == position)?;
+        self.food.swap_remove(index);
+        self.history.push((self.steps, GameEvent::Expire(position)));
+        self.spawn_food().ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(x: i32, y: i32) -> Position {
+        Position { x, y }
+    }
+
+    fn arena(half_width: i32, half_height: i32, mode: ArenaMode) -> Arena {
+        Arena {
+            half_width,
+            half_height,
+            mode,
+        }
+    }
+
+    /// Steps the snake once, taking away any food placed to replace what was eaten.
+    fn advance(game: &mut SnakeGame) -> Result<Step, GameOver> {
+        let step = game.step()?;
+        game.food.retain(|food| Some(*food) != step.spawned);
+        Ok(step)
+    }
+
+    /// Turns the snake and steps it once, panicking if the game ends.
+    fn step_towards(game: &mut SnakeGame, direction: Direction) -> Step {
+        assert!(game.turn(direction));
+        advance(game).unwrap()
+    }
+
+    #[test]
+    fn walled_arena_ends_the_game_at_the_edge() {
+        let mut game = SnakeGame::new(
+            arena(2, 2, ArenaMode::Walled),
+            position(2, 0),
+            Direction::Right,
+            1,
+            0,
+        );
+        assert_eq!(advance(&mut game), Err(GameOver::HitWall));
+    }
+
+    #[test]
+    fn wrap_arena_brings_the_snake_back_on_the_other_side() {
+        let mut game = SnakeGame::new(
+            arena(2, 2, ArenaMode::Wrap),
+            position(2, 0),
+            Direction::Right,
+            1,
+            0,
+        );
+        assert_eq!(advance(&mut game).unwrap().head, position(-2, 0));
+        assert_eq!(
+            step_towards(&mut game, Direction::Down).head,
+            position(-2, -1)
+        );
+        assert_eq!(
+            step_towards(&mut game, Direction::Down).head,
+            position(-2, -2)
+        );
+        assert_eq!(
+            step_towards(&mut game, Direction::Down).head,
+            position(-2, 2)
+        );
+    }
+
+    #[test]
+    fn running_into_the_body_ends_the_game() {
+        let mut game = SnakeGame::new(
+            arena(5, 5, ArenaMode::Walled),
+            position(0, 0),
+            Direction::Right,
+            5,
+            0,
+        );
+        for _ in 0..4 {
+            advance(&mut game).unwrap();
+        }
+        step_towards(&mut game, Direction::Up);
+        step_towards(&mut game, Direction::Left);
+        assert!(game.turn(Direction::Down));
+        assert_eq!(advance(&mut game), Err(GameOver::HitOwnTail));
+    }
+
+    /// A snake whose head is next to its tail at (0, 0), having just eaten `last_meal`.
+    fn snake_beside_its_tail(last_meal: FoodKind) -> SnakeGame {
+        let mut game = SnakeGame::new(
+            arena(5, 5, ArenaMode::Walled),
+            position(0, 0),
+            Direction::Right,
+            1,
+            0,
+        );
+        game.place_food(position(1, 0), FoodKind::Parcel);
+        game.place_food(position(0, 1), last_meal);
+        advance(&mut game).unwrap();
+        step_towards(&mut game, Direction::Up);
+        step_towards(&mut game, Direction::Left);
+        assert_eq!(game.tail(), position(0, 0));
+        game
+    }
+
+    #[test]
+    fn snake_can_follow_its_tail_into_the_cell_it_leaves() {
+        let mut game = snake_beside_its_tail(FoodKind::Letter);
+        let step = step_towards(&mut game, Direction::Down);
+        assert_eq!(step.head, position(0, 0));
+        assert_eq!(game.length(), 4);
+    }
+
+    #[test]
+    fn tail_stays_put_while_the_snake_is_growing() {
+        let mut game = snake_beside_its_tail(FoodKind::Parcel);
+        assert!(game.turn(Direction::Down));
+        assert_eq!(advance(&mut game), Err(GameOver::HitOwnTail));
+    }
+
+    #[test]
+    fn letters_grow_the_snake_by_one_segment() {
+        let mut game = SnakeGame::new(
+            arena(5, 5, ArenaMode::Walled),
+            position(0, 0),
+            Direction::Right,
+            1,
+            0,
+        );
+        game.place_food(position(1, 0), FoodKind::Letter);
+
+        let step = advance(&mut game).unwrap();
+        assert_eq!(step.ate.map(|food| food.kind), Some(FoodKind::Letter));
+        assert!(step.grew);
+        assert!(!advance(&mut game).unwrap().grew);
+        assert_eq!(game.segments_added(), 1);
+        assert_eq!(game.delivered_of(FoodKind::Letter), 1);
+    }
+
+    #[test]
+    fn parcels_grow_the_snake_by_two_segments() {
+        let mut game = SnakeGame::new(
+            arena(5, 5, ArenaMode::Walled),
+            position(0, 0),
+            Direction::Right,
+            1,
+            0,
+        );
+        game.place_food(position(1, 0), FoodKind::Parcel);
+
+        assert!(advance(&mut game).unwrap().grew);
+        assert!(advance(&mut game).unwrap().grew);
+        assert!(!advance(&mut game).unwrap().grew);
+        assert_eq!(game.segments_added(), 2);
+        assert_eq!(game.length(), 3);
+    }
+
+    #[test]
+    fn turning_back_into_the_neck_is_rejected() {
+        let mut game = SnakeGame::new(
+            arena(5, 5, ArenaMode::Walled),
+            position(0, 0),
+            Direction::Right,
+            3,
+            0,
+        );
+        advance(&mut game).unwrap();
+
+        assert!(!game.turn(Direction::Left));
+        assert_eq!(game.heading(), Direction::Right);
+        assert_eq!(advance(&mut game).unwrap().head, position(2, 0));
+    }
+
+    #[test]
+    fn spawning_food_with_no_free_cells_clears_the_board() {
+        let mut game = SnakeGame::new(
+            arena(1, 0, ArenaMode::Walled),
+            position(0, 0),
+            Direction::Right,
+            1,
+            0,
+        );
+
+        assert!(game.spawn_food().is_ok());
+        assert!(game.spawn_food().is_ok());
+        assert_eq!(game.spawn_food(), Err(GameOver::BoardCleared));
+    }
+
+    #[test]
+    fn eaten_food_is_replaced_until_the_board_is_cleared() {
+        let mut game = SnakeGame::new(
+            arena(1, 0, ArenaMode::Walled),
+            position(-1, 0),
+            Direction::Right,
+            1,
+            0,
+        );
+        game.place_food(position(0, 0), FoodKind::Letter);
+
+        let step = game.step().unwrap();
+        assert_eq!(step.spawned.map(|food| food.position), Some(position(1, 0)));
+        assert_eq!(game.step().unwrap().spawned, None);
+        assert_eq!(game.step(), Err(GameOver::BoardCleared));
+        assert_eq!(game.length(), 3);
+    }
+
+    #[test]
+    fn expired_food_is_replaced() {
+        let mut game = SnakeGame::new(
+            arena(1, 0, ArenaMode::Walled),
+            position(-1, 0),
+            Direction::Right,
+            1,
+            0,
+        );
+        game.place_food(position(1, 0), FoodKind::Express);
+
+        let replacement = game.expire_food(position(1, 0)).unwrap();
+        assert_eq!(game.food(), &[replacement]);
+        assert_eq!(game.expire_food(position(-1, 0)), None);
+    }
+
+    #[test]
+    fn games_with_the_same_seed_place_the_same_food() {
+        let food = |seed| {
+            let mut game = SnakeGame::new(
+                arena(5, 5, ArenaMode::Walled),
+                position(0, 0),
+                Direction::Right,
+                1,
+                seed,
+            );
+            (0..5)
+                .map(|_| game.spawn_food().unwrap())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(food(7), food(7));
+    }
+
+    #[test]
+    fn replaying_the_history_plays_the_game_out_the_same_way() {
+        let new_game = || {
+            let mut game = SnakeGame::new(
+                arena(4, 4, ArenaMode::Wrap),
+                position(0, 0),
+                Direction::Up,
+                2,
+                3,
+            );
+            game.spawn_food().unwrap();
+            game
+        };
+
+        let mut original = new_game();
+        let ending = (0..1000)
+            .find_map(|step| {
+                if step % 3 == 0 {
+                    original.turn(Direction::ALL[step * 7 / 5 % 4]);
+                }
+                if step % 7 == 0 {
+                    let position = original.food()[0].position;
+                    original.expire_food(position);
+                }
+                original.step().err()
+            })
+            .unwrap();
+
+        let mut replayed = new_game();
+        let mut history = original.history().iter().peekable();
+        let replayed_ending = loop {
+            while let Some((_, event)) = history.next_if(|(step, _)| *step == replayed.steps()) {
+                replayed.apply(*event);
+            }
+            if let Err(game_over) = replayed.step() {
+                break game_over;
+            }
+        };
+
+        assert_eq!(replayed_ending, ending);
+        assert_eq!(replayed.steps(), original.steps());
+        assert_eq!(
+            replayed.body().collect::<Vec<_>>(),
+            original.body().collect::<Vec<_>>()
+        );
+        assert_eq!(replayed.food(), original.food());
+    }
+}
@@ -0,0 +1,166 @@
+//! Settings page, opened from the pause menu.
+
+use bevy::prelude::*;
+use tracing::instrument;
+
+use crate::{
+    assets::Fonts,
+    focus::{Activated, Focusable},
+    settings::Settings,
+    ui::{BUTTON_COLOR, PANEL_COLOR},
+    GameState,
+};
+
+#[derive(Component)]
+pub struct SettingsPage;
+
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingsItem {
+    Fullscreen,
+    SmoothMovement,
+    Back,
+}
+
+impl SettingsItem {
+    const ALL: [SettingsItem; 3] = [
+        SettingsItem::Fullscreen,
+        SettingsItem::SmoothMovement,
+        SettingsItem::Back,
+    ];
+
+    fn label(self, settings: &Settings) -> String {
+        let on_off = |value| if value { "On" } else { "Off" };
+        match self {
+            SettingsItem::Fullscreen => format!("Fullscreen: {}", on_off(settings.fullscreen)),
+            SettingsItem::SmoothMovement => {
+                format!(
+                    "Smooth snake movement: {}",
+                    on_off(settings.smooth_movement)
+                )
+            }
+            SettingsItem::Back => "Back".to_string(),
+        }
+    }
+}
+
+/// Text showing the current value of a setting.
+#[derive(Component)]
+pub struct SettingLabel(SettingsItem);
+
+#[instrument(skip(commands, fonts, settings))]
+pub fn setup(mut commands: Commands, fonts: Res<Fonts>, settings: Res<Settings>) {
+    info!("setting up settings page");
+
+    let text_style = |font_size| TextStyle {
+        font: fonts.vt323.clone(),
+        font_size,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size {
+                    width: Val::Percent(100.),
+                    height: Val::Percent(100.),
+                },
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..Default::default()
+            },
+            color: PANEL_COLOR.into(),
+            ..Default::default()
+        })
+        .insert(Name::new("Settings Page"))
+        .insert(SettingsPage)
+        .with_children(|parent| {
+            parent.spawn_bundle(TextBundle {
+                style: Style {
+                    margin: Rect::all(Val::Px(24.)),
+                    ..Default::default()
+                },
+                text: Text::with_section("Settings", text_style(64.), Default::default()),
+                ..Default::default()
+            });
+
+            for (index, item) in SettingsItem::ALL.into_iter().enumerate() {
+                parent
+                    .spawn_bundle(ButtonBundle {
+                        style: Style {
+                            margin: Rect::all(Val::Px(8.)),
+                            padding: Rect::all(Val::Px(8.)),
+                            ..Default::default()
+                        },
+                        color: BUTTON_COLOR.into(),
+                        ..Default::default()
+                    })
+                    .insert(item)
+                    .insert(Focusable(index))
+                    .with_children(|parent| {
+                        parent
+                            .spawn_bundle(TextBundle {
+                                text: Text::with_section(
+                                    item.label(&settings),
+                                    text_style(36.),
+                                    Default::default(),
+                                ),
+                                ..Default::default()
+                            })
+                            .insert(SettingLabel(item));
+                    });
+            }
+        });
+}
+
+#[instrument(skip(activated, query, settings, app_state))]
+pub fn click_item(
+    mut activated: EventReader<Activated>,
+    query: Query<&SettingsItem>,
+    mut settings: ResMut<Settings>,
+    mut app_state: ResMut<State<GameState>>,
+) {
+    for item in activated
+        .iter()
+        .filter_map(|Activated(entity)| query.get(*entity).ok())
+    {
+        match item {
+            SettingsItem::Fullscreen => {
+                settings.fullscreen = !settings.fullscreen;
+                info!(fullscreen = settings.fullscreen, "toggled fullscreen");
+                settings.save();
+            }
+            SettingsItem::SmoothMovement => {
+                settings.smooth_movement = !settings.smooth_movement;
+                info!(
+                    smooth_movement = settings.smooth_movement,
+                    "toggled smooth movement"
+                );
+                settings.save();
+            }
+            SettingsItem::Back => {
+                app_state.pop().unwrap();
+            }
+        }
+
+        return;
+    }
+}
+
+/// Keeps the button labels in sync with the settings they show.
+pub fn update_labels(settings: Res<Settings>, mut labels: Query<(&SettingLabel, &mut Text)>) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    for (SettingLabel(item), mut text) in labels.iter_mut() {
+        text.sections[0].value = item.label(&settings);
+    }
+}
+
+#[instrument(skip(commands, query))]
+pub fn teardown(mut commands: Commands, query: Query<Entity, With<SettingsPage>>) {
+    info!("tearing down settings page");
+    query.for_each(|entity| commands.entity(entity).despawn_recursive());
+}
@@ -0,0 +1,49 @@
+//! Player settings that can be changed from the in-game settings page.
+
+use bevy::{prelude::*, window::WindowMode};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+use crate::config;
+
+const SETTINGS_FILE_NAME: &str = "settings.ron";
+
+/// Settings loaded from `settings.ron` in the config directory.
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub fullscreen: bool,
+    /// Slide the snake between cells instead of jumping from one to the next.
+    pub smooth_movement: bool,
+}
+
+impl Settings {
+    pub fn load() -> Self {
+        config::load(SETTINGS_FILE_NAME)
+    }
+
+    pub fn save(&self) {
+        match config::save(SETTINGS_FILE_NAME, self) {
+            Ok(()) => debug!(settings = ?self, "saved settings"),
+            Err(error) => warn!(?error, "failed to save settings"),
+        }
+    }
+}
+
+/// Applies settings to the window whenever they change.
+#[instrument(skip(settings, windows))]
+pub fn apply_settings(settings: Res<Settings>, mut windows: ResMut<Windows>) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    if let Some(window) = windows.get_primary_mut() {
+        let mode = if settings.fullscreen {
+            WindowMode::BorderlessFullscreen
+        } else {
+            WindowMode::Windowed
+        };
+        info!(?mode, "setting window mode");
+        window.set_mode(mode);
+    }
+}
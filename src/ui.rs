@@ -0,0 +1,59 @@
+//! Colors and buttons shared by the game's menus, dialogue and other panels.
+
+use bevy::prelude::*;
+
+use crate::focus::Focusable;
+
+pub const BUTTON_COLOR: Color = Color::rgb(0.55, 0.35, 0.25);
+/// Background of menus that fill the screen.
+pub const PANEL_COLOR: Color = Color::rgb(0.33, 0.2, 0.14);
+/// Background of panels drawn over the game, like the dialogue box.
+pub const OVERLAY_PANEL_COLOR: Color = Color::rgba(0.33, 0.2, 0.14, 0.95);
+/// Text of buttons that can't be picked.
+pub const UNAVAILABLE_COLOR: Color = Color::rgb(0.6, 0.6, 0.6);
+/// Text that's called out, like the focused button, a speaker's name or a new high score.
+pub const HIGHLIGHT_COLOR: Color = Color::rgb(1., 0.85, 0.4);
+
+/// Spawns the button for one of a list of choices, with the given marker if it can be picked.
+///
+/// Choices that can't be picked are greyed out and get neither the marker nor [`Focusable`], so
+/// they can't be focused or picked.
+pub fn spawn_choice_button(
+    parent: &mut ChildBuilder,
+    index: usize,
+    label: String,
+    text_style: TextStyle,
+    available: bool,
+    marker: impl Component,
+) {
+    let mut button = parent.spawn_bundle(ButtonBundle {
+        style: Style {
+            margin: Rect::all(Val::Px(4.)),
+            padding: Rect::all(Val::Px(6.)),
+            ..Default::default()
+        },
+        color: BUTTON_COLOR.into(),
+        ..Default::default()
+    });
+    if available {
+        button.insert(marker).insert(Focusable(index));
+    }
+    button.with_children(|parent| {
+        let color = if available {
+            Color::WHITE
+        } else {
+            UNAVAILABLE_COLOR
+        };
+        parent.spawn_bundle(TextBundle {
+            text: Text::with_section(
+                label,
+                TextStyle {
+                    color,
+                    ..text_style
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        });
+    });
+}